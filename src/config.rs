@@ -0,0 +1,293 @@
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, OnceLock, RwLock};
+use std::time::{Duration, Instant, SystemTime};
+
+use serde::{Deserialize, Serialize};
+use windows::Win32::Foundation::HMODULE;
+use windows::Win32::System::LibraryLoader::GetModuleFileNameW;
+
+use crate::export::ExportConfig;
+use crate::free_camera::{CameraUpdateConfig, FreeCameraKeys};
+use crate::logging::LogLevel;
+use crate::spectate::SpectateKeys;
+
+pub const CONFIG_FILE_NAME: &str = "config.toml";
+
+static CURRENT: RwLock<Option<Arc<Config>>> = RwLock::new(None);
+static DIRECTORY: OnceLock<PathBuf> = OnceLock::new();
+
+/// Everything that can be set from `config.toml`. Anything left out of the
+/// file keeps its default, unknown keys are rejected to catch typos.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub log_level: LogLevel,
+    /// Only read at startup.
+    pub log_file: PathBuf,
+    /// Gzipped list of resource names to resolve hashes with. Relative paths
+    /// start at the config's directory. Only read the first time a hash is
+    /// resolved.
+    pub dictionary: PathBuf,
+    pub window: WindowConfig,
+    pub panels: PanelConfig,
+    pub export: ExportConfig,
+    pub hotkeys: HotkeyConfig,
+    pub features: FeatureConfig,
+    /// Code the free camera patches out, without it the game keeps moving
+    /// the camera back.
+    pub camera_update: Option<CameraUpdateConfig>,
+    /// Offset of the ChrDbg flags from the game's base address, the flags
+    /// panel stays empty without it.
+    pub chr_dbg_flags: Option<usize>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            log_level: LogLevel::default(),
+            log_file: PathBuf::from("debug.log"),
+            dictionary: PathBuf::from("resource_names.txt.gz"),
+            window: WindowConfig::default(),
+            panels: PanelConfig::default(),
+            export: ExportConfig::default(),
+            hotkeys: HotkeyConfig::default(),
+            features: FeatureConfig::default(),
+            camera_update: None,
+            chr_dbg_flags: None,
+        }
+    }
+}
+
+/// Size of the overlay window the first time it's shown.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct WindowConfig {
+    pub width: f32,
+    pub height: f32,
+}
+
+impl Default for WindowConfig {
+    fn default() -> Self {
+        Self { width: 800.0, height: 600.0 }
+    }
+}
+
+/// Overlay panels to show.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct PanelConfig {
+    pub world_chr_man: bool,
+    pub world_chr_man_dbg: bool,
+    pub chr_dbg_flags: bool,
+    pub session_manager: bool,
+    pub session_history: bool,
+    pub session_players: bool,
+    pub session_events: bool,
+    /// Loads two session event logs and diffs their sessions.
+    pub session_diff: bool,
+    pub world_area_time: bool,
+    pub camera: bool,
+    pub free_camera: bool,
+    pub spectate: bool,
+    pub flver_repository: bool,
+    pub input: bool,
+}
+
+impl Default for PanelConfig {
+    fn default() -> Self {
+        Self {
+            world_chr_man: true,
+            world_chr_man_dbg: true,
+            chr_dbg_flags: true,
+            session_manager: true,
+            session_history: true,
+            session_players: true,
+            session_events: true,
+            session_diff: true,
+            world_area_time: true,
+            camera: true,
+            free_camera: true,
+            spectate: true,
+            flver_repository: true,
+            input: true,
+        }
+    }
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct HotkeyConfig {
+    pub spectate: SpectateKeys,
+    pub free_camera: FreeCameraKeys,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct FeatureConfig {
+    pub record_session_events: bool,
+    pub free_camera: bool,
+    pub spectate: bool,
+    /// Keeps input from reaching the game while the overlay has focus.
+    pub capture_input: bool,
+}
+
+impl Default for FeatureConfig {
+    fn default() -> Self {
+        Self {
+            record_session_events: true,
+            free_camera: true,
+            spectate: true,
+            capture_input: true,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum ConfigError {
+    Io(io::Error),
+    Parse(toml::de::Error),
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Io(e) => write!(f, "{e}"),
+            Self::Parse(e) => write!(f, "{e}"),
+        }
+    }
+}
+
+impl std::str::FromStr for Config {
+    type Err = ConfigError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        toml::from_str(s).map_err(ConfigError::Parse)
+    }
+}
+
+/// Config that is currently in effect. Defaults until one is set.
+pub fn current() -> Arc<Config> {
+    CURRENT.read()
+        .unwrap_or_else(|e| e.into_inner())
+        .clone()
+        .unwrap_or_default()
+}
+
+pub fn set_current(config: Config) {
+    *CURRENT.write().unwrap_or_else(|e| e.into_inner()) = Some(Arc::new(config));
+}
+
+/// Sets the directory relative paths in the config start at, the first one
+/// set sticks.
+pub fn set_directory(directory: &Path) {
+    let _ = DIRECTORY.set(directory.to_path_buf());
+}
+
+/// Resolves a path from the config against the config's directory. Paths are
+/// left alone if they're absolute or no directory has been set.
+pub fn resolve_path(path: &Path) -> PathBuf {
+    match DIRECTORY.get() {
+        Some(directory) => directory.join(path),
+        None => path.to_path_buf(),
+    }
+}
+
+/// Directory the module was loaded from, used to find the config next to
+/// the DLL.
+pub fn module_directory(hmodule: usize) -> Option<PathBuf> {
+    let mut buffer = [0u16; 0x400];
+    let length = unsafe { GetModuleFileNameW(HMODULE(hmodule as isize), &mut buffer) } as usize;
+
+    // A full buffer means the path got truncated.
+    if length == 0 || length == buffer.len() {
+        return None;
+    }
+
+    let path = PathBuf::from(String::from_utf16(&buffer[..length]).ok()?);
+    path.parent().map(Path::to_path_buf)
+}
+
+/// Reloads the config whenever the file changes on disk. A missing file
+/// counts as an empty one, a broken file keeps the error around until the
+/// next successful load.
+pub struct ConfigWatcher {
+    path: PathBuf,
+    interval: Duration,
+    last_check: Option<Instant>,
+    /// Modification time of the file at the last load, None if it was
+    /// missing. Not set before the first load.
+    loaded_modified: Option<Option<SystemTime>>,
+    error: Option<ConfigError>,
+}
+
+impl ConfigWatcher {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self {
+            path: path.into(),
+            interval: Duration::from_secs(1),
+            last_check: None,
+            loaded_modified: None,
+            error: None,
+        }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Error from the last load, if it failed.
+    pub fn error(&self) -> Option<&ConfigError> {
+        self.error.as_ref()
+    }
+
+    /// Loads the config if the file changed since the last load. Checks the
+    /// file at most once per interval.
+    pub fn reload_if_changed(&mut self, now: Instant) -> Option<Config> {
+        let checked_recently = self.last_check
+            .is_some_and(|last_check| now.duration_since(last_check) < self.interval);
+        if checked_recently {
+            return None;
+        }
+        self.last_check = Some(now);
+
+        let modified = fs::metadata(&self.path)
+            .and_then(|metadata| metadata.modified())
+            .ok();
+        if self.loaded_modified == Some(modified) {
+            return None;
+        }
+
+        self.reload()
+    }
+
+    /// Loads the config regardless of it having changed. Returns None if the
+    /// file couldn't be loaded, the error is kept.
+    pub fn reload(&mut self) -> Option<Config> {
+        self.loaded_modified = Some(
+            fs::metadata(&self.path)
+                .and_then(|metadata| metadata.modified())
+                .ok(),
+        );
+
+        let result = match fs::read_to_string(&self.path) {
+            Ok(contents) => contents.parse::<Config>(),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(Config::default()),
+            Err(e) => Err(ConfigError::Io(e)),
+        };
+
+        match result {
+            Ok(config) => {
+                self.error = None;
+                Some(config)
+            },
+            Err(e) => {
+                tracing::error!("Could not load {}: {e}", self.path.display());
+                self.error = Some(e);
+                None
+            },
+        }
+    }
+}
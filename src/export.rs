@@ -1,29 +1,302 @@
 use std::fs;
 use std::io;
 use std::io::Write;
+use std::path::PathBuf;
+use std::time::{SystemTime, UNIX_EPOCH};
 
+use serde::{Deserialize, Serialize};
+
+use crate::config;
+use crate::game::cs::WorldChrMan;
+use crate::game::fd4::FlverRepository;
+use crate::session::SessionRoster;
+use crate::util::hash_dictionary::{get_dictionary, HashDictionary};
 use crate::util::singleton::SingletonMap;
 
 #[derive(Debug)]
 pub enum ExportError {
     FileCreation(io::Error),
     FileWrite(io::Error),
+    Serialize(serde_json::Error),
 }
 
-pub trait Export {
-    fn export(&self) -> Result<(), ExportError>;
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ExportFormat {
+    /// Comma separated values with a header line.
+    #[default]
+    Csv,
+    /// One JSON object per line.
+    Json,
 }
 
-impl Export for SingletonMap {
+impl ExportFormat {
+    pub const fn extension(&self) -> &'static str {
+        match self {
+            Self::Csv => "csv",
+            Self::Json => "jsonl",
+        }
+    }
+}
+
+/// Where exports end up. File names are without extension, the format
+/// decides on that.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ExportConfig {
+    pub directory: PathBuf,
+    pub format: ExportFormat,
+    pub singletons: String,
+    pub flver_repository: String,
+    pub unknown_hashes: String,
+    pub characters: String,
+    pub session_roster: String,
+    /// The session event log is always JSON lines and rotates on its own.
+    pub session_events: String,
+    pub session_events_max_file_size: u64,
+    pub session_events_max_files: usize,
+}
+
+impl Default for ExportConfig {
+    fn default() -> Self {
+        Self {
+            directory: PathBuf::from("."),
+            format: ExportFormat::Csv,
+            singletons: "singletons".to_string(),
+            flver_repository: "flver_repository".to_string(),
+            unknown_hashes: "unknown_hashes".to_string(),
+            characters: "characters".to_string(),
+            session_roster: "session_roster".to_string(),
+            session_events: "session_events".to_string(),
+            session_events_max_file_size: 0x100000,
+            session_events_max_files: 5,
+        }
+    }
+}
+
+impl ExportConfig {
+    pub fn path(&self, file_name: &str) -> PathBuf {
+        self.directory.join(format!("{file_name}.{}", self.format.extension()))
+    }
+}
+
+pub trait Export {
+    type Record: Serialize;
+
+    /// Adds to the end of the file instead of replacing it.
+    const APPEND: bool = false;
+
+    /// Picks the file name out of the export config.
+    fn file_name(config: &ExportConfig) -> &str;
+
+    fn records(&self) -> Vec<Self::Record>;
+
+    /// Writes the records to the file and in the format from the current
+    /// config.
     fn export(&self) -> Result<(), ExportError> {
-        let mut fh = fs::File::create("./singletons.csv")
+        let config = &config::current().export;
+        let path = config.path(Self::file_name(config));
+
+        let mut fh = fs::OpenOptions::new()
+            .create(true)
+            .write(true)
+            .append(Self::APPEND)
+            .truncate(!Self::APPEND)
+            .open(path)
             .map_err(ExportError::FileCreation)?;
 
-        for entry in self.iter() {
-            writeln!(fh, "\"{}\", {:x}", entry.0, entry.1)
-                .map_err(ExportError::FileWrite)?;
-        }
+        // Appending to an existing file shouldn't repeat the header.
+        let is_empty = fh.metadata()
+            .map(|metadata| metadata.len() == 0)
+            .map_err(ExportError::FileWrite)?;
+
+        write_records(&mut fh, &self.records(), config.format, is_empty)
+    }
+}
+
+/// Writes records in the given format. CSV columns are the record's fields in
+/// declaration order, strings are quoted and missing values are left empty.
+pub fn write_records<R: Serialize>(
+    writer: &mut impl Write,
+    records: &[R],
+    format: ExportFormat,
+    header: bool,
+) -> Result<(), ExportError> {
+    for (index, record) in records.iter().enumerate() {
+        let value = serde_json::to_value(record).map_err(ExportError::Serialize)?;
+
+        match format {
+            ExportFormat::Json => writeln!(writer, "{value}"),
+            ExportFormat::Csv => {
+                let fields = match value {
+                    serde_json::Value::Object(fields) => fields.into_iter().collect(),
+                    value => vec![("value".to_string(), value)],
+                };
+
+                if header && index == 0 {
+                    let names = fields.iter().map(|(name, _)| name.as_str()).collect::<Vec<_>>();
+                    writeln!(writer, "{}", names.join(", ")).map_err(ExportError::FileWrite)?;
+                }
+
+                let values = fields.iter().map(|(_, value)| csv_value(value)).collect::<Vec<_>>();
+                writeln!(writer, "{}", values.join(", "))
+            },
+        }.map_err(ExportError::FileWrite)?;
+    }
+
+    Ok(())
+}
+
+fn csv_value(value: &serde_json::Value) -> String {
+    match value {
+        serde_json::Value::Null => String::new(),
+        serde_json::Value::String(s) => format!("\"{}\"", s.replace('"', "\"\"")),
+        value => value.to_string(),
+    }
+}
+
+#[derive(Serialize)]
+pub struct SingletonRecord {
+    pub name: String,
+    pub address: String,
+}
+
+impl Export for SingletonMap {
+    type Record = SingletonRecord;
 
-        Ok(())
+    fn file_name(config: &ExportConfig) -> &str {
+        &config.singletons
     }
+
+    fn records(&self) -> Vec<Self::Record> {
+        self.iter()
+            .map(|(name, address)| SingletonRecord {
+                name: name.clone(),
+                address: format!("{address:x}"),
+            })
+            .collect()
+    }
+}
+
+#[derive(Serialize)]
+pub struct ResourceRecord {
+    pub name: String,
+    pub hash: String,
+}
+
+impl Export for FlverRepository<'_> {
+    type Record = ResourceRecord;
+
+    fn file_name(config: &ExportConfig) -> &str {
+        &config.flver_repository
+    }
+
+    fn records(&self) -> Vec<Self::Record> {
+        let dictionary = get_dictionary();
+
+        // Unlocked, the mutex guarding this holder hasn't been found yet so
+        // this can race the loader.
+        self.map.iter()
+            .map(|res_cap| ResourceRecord {
+                name: dictionary.display_name(&res_cap.header.name).to_string(),
+                hash: format!("{:08x}", res_cap.header.name.computed_hash()),
+            })
+            .collect()
+    }
+}
+
+#[derive(Serialize)]
+pub struct UnknownHashRecord {
+    pub hash: String,
+}
+
+/// Exports the hashes that couldn't be resolved so far, such that they can
+/// be looked into and added to the name list.
+impl Export for HashDictionary {
+    type Record = UnknownHashRecord;
+
+    fn file_name(config: &ExportConfig) -> &str {
+        &config.unknown_hashes
+    }
+
+    fn records(&self) -> Vec<Self::Record> {
+        self.unknown_hashes()
+            .into_iter()
+            .map(|hash| UnknownHashRecord { hash: format!("{hash:08x}") })
+            .collect()
+    }
+}
+
+#[derive(Serialize)]
+pub struct CharacterRecord {
+    pub handle: String,
+    pub origin: String,
+    pub map_id: String,
+    pub x: f32,
+    pub y: f32,
+    pub z: f32,
+}
+
+impl Export for WorldChrMan<'_> {
+    type Record = CharacterRecord;
+
+    fn file_name(config: &ExportConfig) -> &str {
+        &config.characters
+    }
+
+    fn records(&self) -> Vec<Self::Record> {
+        self.characters()
+            .map(|entry| {
+                let chr_ins = entry.chr_ins;
+                let position = chr_ins.position().unwrap_or_default();
+
+                CharacterRecord {
+                    handle: chr_ins.field_ins_handle.to_string(),
+                    origin: format!("{:?}", entry.origin),
+                    map_id: chr_ins.map_id_1.to_string(),
+                    x: position.x,
+                    y: position.y,
+                    z: position.z,
+                }
+            })
+            .collect()
+    }
+}
+
+#[derive(Serialize)]
+pub struct SessionPlayerRecord {
+    pub steam_id: u64,
+    pub session_slot: i32,
+    pub joined_at: u64,
+    pub left_at: Option<u64>,
+}
+
+/// Appends to the log so rosters of several sessions can be reviewed
+/// afterwards. Times are in seconds since the unix epoch.
+impl Export for SessionRoster {
+    type Record = SessionPlayerRecord;
+
+    const APPEND: bool = true;
+
+    fn file_name(config: &ExportConfig) -> &str {
+        &config.session_roster
+    }
+
+    fn records(&self) -> Vec<Self::Record> {
+        self.players()
+            .iter()
+            .map(|player| SessionPlayerRecord {
+                steam_id: player.steam_id,
+                session_slot: player.session_slot,
+                joined_at: unix_seconds(player.joined_at),
+                left_at: player.left_at.map(unix_seconds),
+            })
+            .collect()
+    }
+}
+
+fn unix_seconds(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_secs())
+        .unwrap_or_default()
 }
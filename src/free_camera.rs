@@ -0,0 +1,359 @@
+use std::f32::consts::FRAC_PI_2;
+
+use serde::{Deserialize, Serialize};
+use windows::core::{HRESULT, PCWSTR};
+use windows::Win32::System::LibraryLoader::GetModuleHandleW;
+
+use crate::game::cs::CSCam;
+use crate::game::matrix::{Matrix4X4, Vector4};
+use crate::util::input::{self, KeyBinding, KeyboardState, Modifiers};
+use crate::util::patch::{self, ActiveState, InactiveState, Patch, PatchError};
+use crate::util::singleton::{self, SectionLookupError};
+
+const CAMERA_UPDATE_LENGTH: usize = 16;
+
+/// Where the code writing the view matrix into the pers cam lives. There is
+/// no default as the offset changes between game versions, it has to be
+/// looked up for the version being run.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct CameraUpdateConfig {
+    /// Offset from the game's base address.
+    pub rva: usize,
+    /// The 16 bytes expected at the offset. Nothing gets patched unless they
+    /// match so an offset for another version can't patch unrelated code.
+    pub original: Vec<u8>,
+}
+
+#[derive(Debug)]
+pub enum CameraUpdateError {
+    NotConfigured,
+    /// The expected bytes don't cover the patch.
+    Length(usize),
+    NoGameBase(HRESULT),
+    Section(SectionLookupError),
+    /// The offset doesn't point into the game's code.
+    OutsideText(usize),
+    /// Holds what was found instead of the expected bytes.
+    Mismatch(Vec<u8>),
+}
+
+/// Checks the configured camera update against the game's code.
+pub fn locate_camera_update(config: &CameraUpdateConfig) -> Result<*mut u8, CameraUpdateError> {
+    let expected = <[u8; CAMERA_UPDATE_LENGTH]>::try_from(config.original.as_slice())
+        .map_err(|_| CameraUpdateError::Length(config.original.len()))?;
+
+    let base = unsafe { GetModuleHandleW(PCWSTR::null()) }
+        .map_err(|e| CameraUpdateError::NoGameBase(e.code()))?
+        .0 as usize;
+    let (text_range, text_slice) = singleton::get_section(".text")
+        .map_err(CameraUpdateError::Section)?;
+
+    let start = base.checked_add(config.rva)
+        .filter(|start| text_range.start <= *start && start + CAMERA_UPDATE_LENGTH <= text_range.end)
+        .ok_or(CameraUpdateError::OutsideText(config.rva))?;
+
+    let found = &text_slice[start - text_range.start..][..CAMERA_UPDATE_LENGTH];
+    if found != expected {
+        return Err(CameraUpdateError::Mismatch(found.to_vec()));
+    }
+
+    Ok(start as *mut u8)
+}
+
+enum CameraUpdatePatch {
+    Unavailable(CameraUpdateError),
+    Inactive(Patch<InactiveState<CAMERA_UPDATE_LENGTH>>),
+    Active(Patch<ActiveState<CAMERA_UPDATE_LENGTH>>),
+}
+
+impl CameraUpdatePatch {
+    fn apply(&mut self) -> Result<(), PatchError> {
+        if let Self::Inactive(patch) = self {
+            *self = Self::Active(patch.apply()?);
+        }
+
+        Ok(())
+    }
+
+    fn rollback(&mut self) -> Result<(), PatchError> {
+        if let Self::Active(patch) = self {
+            *self = Self::Inactive(patch.rollback()?);
+        }
+
+        Ok(())
+    }
+}
+
+/// Movement requested for a single update. Movement is relative to where
+/// the camera is looking.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct FreeCameraInput {
+    pub forward: f32,
+    pub right: f32,
+    pub up: f32,
+    /// Turning in radians.
+    pub yaw: f32,
+    pub pitch: f32,
+    /// Change in field of view in radians.
+    pub fov: f32,
+    pub fast: bool,
+}
+
+impl FreeCameraInput {
+    /// Reads the keyboard and mouse. The mouse looks around while the look
+    /// binding is held.
+    pub fn poll(
+        keyboard: &KeyboardState,
+        keys: &FreeCameraKeys,
+        cursor: &mut input::CursorDelta,
+        sensitivity: f32,
+        dt: f32,
+    ) -> Self {
+        let axis = |positive: KeyBinding, negative: KeyBinding| {
+            positive.is_down(keyboard) as i32 as f32 - negative.is_down(keyboard) as i32 as f32
+        };
+
+        let (dx, dy) = match keys.look.is_down(keyboard) {
+            true => cursor.update(input::cursor_position()),
+            false => {
+                cursor.reset();
+                (0, 0)
+            },
+        };
+
+        Self {
+            forward: axis(keys.forward, keys.back),
+            right: axis(keys.right, keys.left),
+            up: axis(keys.up, keys.down),
+            yaw: dx as f32 * sensitivity,
+            pitch: -dy as f32 * sensitivity,
+            fov: axis(keys.zoom_out, keys.zoom_in) * dt,
+            fast: keys.fast.is_down(keyboard),
+        }
+    }
+}
+
+/// Hotkeys for the free camera, keys left out of a config keep their default.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct FreeCameraKeys {
+    pub toggle: KeyBinding,
+    pub forward: KeyBinding,
+    pub back: KeyBinding,
+    pub left: KeyBinding,
+    pub right: KeyBinding,
+    pub up: KeyBinding,
+    pub down: KeyBinding,
+    pub zoom_in: KeyBinding,
+    pub zoom_out: KeyBinding,
+    pub fast: KeyBinding,
+    pub look: KeyBinding,
+}
+
+impl Default for FreeCameraKeys {
+    /// Ctrl+F toggles, WASD moves, Q and E move down and up, Z and X zoom,
+    /// shift speeds up and the right mouse button looks around.
+    fn default() -> Self {
+        Self {
+            toggle: KeyBinding::with_modifiers(0x46, Modifiers { ctrl: true, ..Modifiers::NONE }),
+            forward: KeyBinding::new(0x57),
+            back: KeyBinding::new(0x53),
+            left: KeyBinding::new(0x41),
+            right: KeyBinding::new(0x44),
+            up: KeyBinding::new(0x45),
+            down: KeyBinding::new(0x51),
+            zoom_in: KeyBinding::new(0x5a),
+            zoom_out: KeyBinding::new(0x58),
+            fast: KeyBinding::new(input::KEY_SHIFT),
+            look: KeyBinding::new(0x02),
+        }
+    }
+}
+
+/// What the pers cam looked like before the free camera took over.
+struct SavedCamera {
+    view_matrix: Matrix4X4,
+    fov: f32,
+}
+
+/// Flies the camera around independently of the player. While active the
+/// game's own camera update is patched out and the view matrix is written by
+/// us instead.
+pub struct FreeCamera {
+    pub position: Vector4,
+    /// Rotation around the y axis in radians, 0 looks down +z.
+    pub yaw: f32,
+    /// Rotation up or down in radians.
+    pub pitch: f32,
+    pub fov: f32,
+    /// Units per second.
+    pub speed: f32,
+    /// Speed multiplier while sprinting.
+    pub fast_multiplier: f32,
+    saved: Option<SavedCamera>,
+    update_patch: CameraUpdatePatch,
+    /// Why applying or rolling back the update patch failed last.
+    patch_error: Option<PatchError>,
+}
+
+impl Default for FreeCamera {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl FreeCamera {
+    const MAX_PITCH: f32 = FRAC_PI_2 - 0.01;
+    const MIN_FOV: f32 = 0.1;
+    const MAX_FOV: f32 = 3.0;
+
+    /// Builds a free camera without an update patch, the game keeps
+    /// overwriting the camera every frame until one is configured.
+    pub fn new() -> Self {
+        Self::with_update_patch(CameraUpdatePatch::Unavailable(CameraUpdateError::NotConfigured))
+    }
+
+    /// Builds a free camera that patches out the camera update at the given
+    /// address while active.
+    ///
+    /// # Safety
+    /// The target has to be 16 bytes of code that can be skipped without
+    /// taking down the game, and stay mapped for as long as the camera
+    /// exists.
+    pub unsafe fn with_update_target(target: *mut u8) -> Self {
+        Self::with_update_patch(Self::update_patch(target))
+    }
+
+    unsafe fn update_patch(target: *mut u8) -> CameraUpdatePatch {
+        CameraUpdatePatch::Inactive(patch::new_patch(target, [0x90; CAMERA_UPDATE_LENGTH]))
+    }
+
+    fn with_update_patch(update_patch: CameraUpdatePatch) -> Self {
+        Self {
+            position: Vector4::default(),
+            yaw: 0.0,
+            pitch: 0.0,
+            fov: 1.0,
+            speed: 10.0,
+            fast_multiplier: 5.0,
+            saved: None,
+            update_patch,
+            patch_error: None,
+        }
+    }
+
+    /// Looks for the camera update from the config. Left alone while active
+    /// as the current patch would have to be rolled back first.
+    pub fn configure_update_patch(&mut self, config: Option<&CameraUpdateConfig>) {
+        if self.is_active() {
+            return;
+        }
+
+        self.update_patch = match config.map(locate_camera_update) {
+            // The bytes were checked to be the camera update in the game's
+            // code, which stays mapped for as long as the game runs.
+            Some(Ok(target)) => unsafe { Self::update_patch(target) },
+            Some(Err(e)) => CameraUpdatePatch::Unavailable(e),
+            None => CameraUpdatePatch::Unavailable(CameraUpdateError::NotConfigured),
+        };
+    }
+
+    pub fn is_active(&self) -> bool {
+        self.saved.is_some()
+    }
+
+    pub fn has_update_patch(&self) -> bool {
+        !matches!(self.update_patch, CameraUpdatePatch::Unavailable(_))
+    }
+
+    /// Why there is no update patch to apply.
+    pub fn update_patch_error(&self) -> Option<&CameraUpdateError> {
+        match &self.update_patch {
+            CameraUpdatePatch::Unavailable(e) => Some(e),
+            _ => None,
+        }
+    }
+
+    /// Why the update patch couldn't be applied or rolled back last time.
+    pub fn patch_error(&self) -> Option<&PatchError> {
+        self.patch_error.as_ref()
+    }
+
+    /// Detaches the camera, starting out from wherever the camera is now.
+    pub fn enable(&mut self, cam: &mut CSCam) {
+        if self.is_active() {
+            return;
+        }
+
+        let forward = cam.forward().normalize();
+        self.position = cam.position();
+        self.yaw = forward.x.atan2(forward.z);
+        self.pitch = forward.y.clamp(-1.0, 1.0).asin().clamp(-Self::MAX_PITCH, Self::MAX_PITCH);
+        self.fov = cam.fov;
+        self.saved = Some(SavedCamera { view_matrix: cam.view_matrix, fov: cam.fov });
+
+        self.patch_error = self.update_patch.apply().err();
+    }
+
+    /// Snaps the camera back to where it was detached and hands it back to the
+    /// game.
+    pub fn disable(&mut self, cam: &mut CSCam) {
+        let Some(saved) = self.saved.take() else {
+            return;
+        };
+
+        self.patch_error = self.update_patch.rollback().err();
+        cam.view_matrix = saved.view_matrix;
+        cam.fov = saved.fov;
+    }
+
+    /// Moves the camera and writes it to the pers cam. Does nothing while the
+    /// free camera is inactive.
+    pub fn update(&mut self, cam: &mut CSCam, input: &FreeCameraInput, dt: f32) {
+        if !self.is_active() {
+            return;
+        }
+
+        self.yaw += input.yaw;
+        self.pitch = (self.pitch + input.pitch).clamp(-Self::MAX_PITCH, Self::MAX_PITCH);
+        self.fov = (self.fov + input.fov).clamp(Self::MIN_FOV, Self::MAX_FOV);
+
+        let speed = match input.fast {
+            true => self.speed * self.fast_multiplier,
+            false => self.speed,
+        };
+
+        let (forward, right, up) = self.axes();
+        let movement = forward * input.forward + right * input.right + up * input.up;
+        self.position = self.position + movement.normalize() * (speed * dt);
+        self.position.w = 1.0;
+
+        cam.view_matrix = self.view_matrix();
+        cam.fov = self.fov;
+    }
+
+    /// Camera transform in the same layout as CSCam's view matrix.
+    pub fn view_matrix(&self) -> Matrix4X4 {
+        let (forward, right, up) = self.axes();
+
+        Matrix4X4::from_rows([
+            [right.x, right.y, right.z, 0.0],
+            [up.x, up.y, up.z, 0.0],
+            [forward.x, forward.y, forward.z, 0.0],
+            [self.position.x, self.position.y, self.position.z, 1.0],
+        ])
+    }
+
+    /// Forward, right and up of the camera.
+    fn axes(&self) -> (Vector4, Vector4, Vector4) {
+        let forward = Vector4::new(
+            self.yaw.sin() * self.pitch.cos(),
+            self.pitch.sin(),
+            self.yaw.cos() * self.pitch.cos(),
+        );
+        let right = Vector4::new(self.yaw.cos(), 0.0, -self.yaw.sin());
+
+        (forward, right, forward.cross(&right))
+    }
+}
@@ -1,16 +1,24 @@
 mod camera;
+mod chr_dbg;
 mod chr_ins;
+mod chr_query;
 mod ez_select_bot;
+mod field_ins;
 mod file;
+mod map_id;
 mod task_group;
 mod world_chr_man;
 mod world_chr_man_dbg;
 mod session_manager;
 
 pub use camera::*;
+pub use chr_dbg::*;
 pub use chr_ins::*;
+pub use chr_query::*;
 pub use ez_select_bot::*;
+pub use field_ins::*;
 pub use file::*;
+pub use map_id::*;
 pub use task_group::*;
 pub use world_chr_man::*;
 pub use world_chr_man_dbg::*;
@@ -1,6 +1,6 @@
 use zerocopy::{FromBytes, FromZeroes};
 
-use crate::game::matrix::Matrix4X4;
+use crate::game::matrix::{Matrix4X4, Vector2, Vector4};
 use crate::util::singleton::DLRFLocatable;
 
 #[repr(C)]
@@ -25,7 +25,11 @@ pub struct CSCam {
     pub vftable: usize,
     pub unk8: u32,
     pub unkc: u32,
+    /// Despite the name this places the camera in the world rather than the
+    /// world in front of the camera. Rows hold the right, up and forward
+    /// axes followed by the position.
     pub view_matrix: Matrix4X4,
+    /// Vertical field of view in radians.
     pub fov: f32,
     pub aspect_ratio: f32,
     pub near_plane: f32,
@@ -33,3 +37,63 @@ pub struct CSCam {
 }
 
 pub type CSPersCam = CSCam;
+
+impl CSCam {
+    pub fn position(&self) -> Vector4 {
+        let [x, y, z, _] = self.view_matrix.3.to_array();
+        Vector4 { x, y, z, w: 1.0 }
+    }
+
+    pub fn right(&self) -> Vector4 {
+        let [x, y, z, _] = self.view_matrix.0.to_array();
+        Vector4::new(x, y, z)
+    }
+
+    pub fn up(&self) -> Vector4 {
+        let [x, y, z, _] = self.view_matrix.1.to_array();
+        Vector4::new(x, y, z)
+    }
+
+    pub fn forward(&self) -> Vector4 {
+        let [x, y, z, _] = self.view_matrix.2.to_array();
+        Vector4::new(x, y, z)
+    }
+
+    /// Transforms world space into camera space.
+    pub fn world_to_view(&self) -> Option<Matrix4X4> {
+        self.view_matrix.inverse()
+    }
+
+    /// Left-handed perspective projection mapping depth to 0 at the near
+    /// plane and 1 at the far plane.
+    pub fn projection_matrix(&self) -> Matrix4X4 {
+        let y_scale = 1.0 / (self.fov / 2.0).tan();
+        let x_scale = y_scale / self.aspect_ratio;
+        let depth = self.far_plane / (self.far_plane - self.near_plane);
+
+        Matrix4X4::from_rows([
+            [x_scale, 0.0, 0.0, 0.0],
+            [0.0, y_scale, 0.0, 0.0],
+            [0.0, 0.0, depth, 1.0],
+            [0.0, 0.0, -self.near_plane * depth, 0.0],
+        ])
+    }
+
+    /// Projects a world position onto a screen of the given size. Positions
+    /// behind the camera have no place on the screen, positions beside it
+    /// end up outside of the screen bounds.
+    pub fn world_to_screen(&self, position: Vector4, screen_size: Vector2) -> Option<Vector2> {
+        let clip = (self.world_to_view()? * self.projection_matrix())
+            .transform_point(position);
+
+        if clip.w <= 0.0 {
+            return None;
+        }
+
+        let (x, y) = (clip.x / clip.w, clip.y / clip.w);
+        Some(Vector2::new(
+            (x + 1.0) / 2.0 * screen_size.x,
+            (1.0 - y) / 2.0 * screen_size.y,
+        ))
+    }
+}
@@ -0,0 +1,144 @@
+use std::ptr;
+
+use windows::core::{HRESULT, PCWSTR};
+use windows::Win32::System::LibraryLoader::GetModuleHandleW;
+use zerocopy::{AsBytes, FromBytes, FromZeroes};
+
+use crate::util::singleton::{self, SectionLookupError};
+
+#[derive(Debug)]
+pub enum ChrDbgFlagsError {
+    NotConfigured,
+    NoGameBase(HRESULT),
+    Section(SectionLookupError),
+    /// The offset doesn't point into the game's data.
+    OutsideData(usize),
+    /// Holds what was found, a block of flags only holds 0s and 1s.
+    NotFlags(ChrDbgFlags),
+}
+
+/// Finds the flag block at an offset from the game's base address. The flags
+/// are plain globals rather than part of a singleton and there is no default
+/// offset as it changes between game versions.
+pub fn locate_chr_dbg_flags(rva: usize) -> Result<ChrDbgFlagsRef, ChrDbgFlagsError> {
+    let base = unsafe { GetModuleHandleW(PCWSTR::null()) }
+        .map_err(|e| ChrDbgFlagsError::NoGameBase(e.code()))?
+        .0 as usize;
+    let (data_range, _) = singleton::get_section(".data")
+        .map_err(ChrDbgFlagsError::Section)?;
+
+    let start = base.checked_add(rva)
+        .filter(|start| data_range.start <= *start && start + std::mem::size_of::<ChrDbgFlags>() <= data_range.end)
+        .and_then(|start| ptr::NonNull::new(start as *mut ChrDbgFlags))
+        .ok_or(ChrDbgFlagsError::OutsideData(rva))?;
+
+    // The range was checked to lie within the game's data, which stays
+    // mapped for as long as the game runs.
+    unsafe { ChrDbgFlagsRef::new(start) }
+}
+
+/// A block of 16 debug toggles. Which byte toggles what hasn't been mapped,
+/// so the fields are named after their offsets. The game only ever seems to
+/// store 0 or 1 in them.
+#[repr(C)]
+#[derive(FromBytes, FromZeroes, AsBytes, Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct ChrDbgFlags {
+    pub unk0: u8,
+    pub unk1: u8,
+    pub unk2: u8,
+    pub unk3: u8,
+    pub unk4: u8,
+    pub unk5: u8,
+    pub unk6: u8,
+    pub unk7: u8,
+    pub unk8: u8,
+    pub unk9: u8,
+    pub unka: u8,
+    pub unkb: u8,
+    pub unkc: u8,
+    pub unkd: u8,
+    pub unke: u8,
+    pub unkf: u8,
+}
+
+impl ChrDbgFlags {
+    pub const COUNT: usize = 0x10;
+
+    /// Whether every byte is a 0 or a 1.
+    pub fn looks_like_flags(&self) -> bool {
+        self.as_bytes().iter().all(|flag| *flag <= 1)
+    }
+
+    pub fn get(&self, index: usize) -> Option<bool> {
+        self.as_bytes().get(index).map(|flag| *flag != 0)
+    }
+
+    /// Offsets of the flags that are set.
+    pub fn enabled(&self) -> impl Iterator<Item = usize> + '_ {
+        (0..Self::COUNT).filter(|index| self.get(*index) == Some(true))
+    }
+}
+
+/// Access to the game's flag block. Every access is a single volatile byte
+/// read or write as the game reads the flags from its own threads, no
+/// reference to the block is ever handed out.
+pub struct ChrDbgFlagsRef {
+    flags: ptr::NonNull<ChrDbgFlags>,
+}
+
+// The block is plain bytes owned by the game and never freed, and every
+// access is a single byte so there is nothing to tear.
+unsafe impl Send for ChrDbgFlagsRef {}
+unsafe impl Sync for ChrDbgFlagsRef {}
+
+impl ChrDbgFlagsRef {
+    /// Checks that the block holds nothing but 0s and 1s before handing out
+    /// access to it, so a wrong offset is less likely to end up poking at
+    /// unrelated game state.
+    ///
+    /// # Safety
+    /// The pointer has to point at 16 bytes that stay mapped and writable for
+    /// as long as the returned value lives.
+    pub unsafe fn new(flags: ptr::NonNull<ChrDbgFlags>) -> Result<Self, ChrDbgFlagsError> {
+        let result = Self { flags };
+
+        let current = result.read();
+        if !current.looks_like_flags() {
+            return Err(ChrDbgFlagsError::NotFlags(current));
+        }
+
+        Ok(result)
+    }
+
+    /// Copies the flags out of the game.
+    pub fn read(&self) -> ChrDbgFlags {
+        let mut result = ChrDbgFlags::default();
+        for (index, flag) in result.as_bytes_mut().iter_mut().enumerate() {
+            *flag = unsafe { ptr::read_volatile(self.byte(index)) };
+        }
+        result
+    }
+
+    pub fn get(&self, index: usize) -> Option<bool> {
+        (index < ChrDbgFlags::COUNT)
+            .then(|| unsafe { ptr::read_volatile(self.byte(index)) } != 0)
+    }
+
+    /// Sets a flag, indices past the block are ignored.
+    pub fn set(&self, index: usize, enabled: bool) {
+        if index < ChrDbgFlags::COUNT {
+            unsafe { ptr::write_volatile(self.byte(index), enabled as u8) };
+        }
+    }
+
+    /// Turns every flag off.
+    pub fn clear(&self) {
+        for index in 0..ChrDbgFlags::COUNT {
+            self.set(index, false);
+        }
+    }
+
+    fn byte(&self, index: usize) -> *mut u8 {
+        self.flags.as_ptr().cast::<u8>().wrapping_add(index)
+    }
+}
@@ -1,40 +1,177 @@
 use std::ffi;
+use std::marker::PhantomData;
 
 use zerocopy::{FromBytes, FromZeroes};
 
-use crate::game::cs::ChrSetEntry;
+use crate::game::cs::{ChrSetEntry, FieldInsHandle, MapId};
+use crate::game::matrix::Vector4;
 
 #[repr(C)]
 #[derive(FromBytes, FromZeroes)]
 pub struct ChrIns<'a> {
     pub vftable: usize,
-    pub field_ins_handle: u32,
-    pub padc: u32,
+    pub field_ins_handle: FieldInsHandle,
     chr_set_entry: usize,
     pub unk18: usize,
     pub unk20: u32,
     pub unk24: u32,
     pub chr_res: usize,
-    pub map_id_1: u32,
-    pub map_id_origin_1: u32,
-    pub map_id_2: u32,
-    pub map_id_origin_2: u32,
+    pub map_id_1: MapId,
+    pub map_id_origin_1: MapId,
+    pub map_id_2: MapId,
+    pub map_id_origin_2: MapId,
     pub unk40: u32,
     pub unk44: u32,
     pub unk48: usize,
     pub chr_model: usize,
-    pub chr_ctrl: &'a mut ChrCtrl<'a>,
+    /// Address of the ChrCtrl, use the accessors to follow it.
+    pub chr_ctrl: usize,
+    unk60: [u8; 0x130],
+    /// Offset comes from the crate's original ChrIns mapping, it hasn't been
+    /// checked against a capture since.
+    pub module_container: *const ChrInsModuleContainer<'a>,
+    _lifetime: PhantomData<&'a ()>,
+}
+
+impl<'a> ChrIns<'a> {
+    pub fn chr_ctrl(&self) -> Option<&ChrCtrl> {
+        unsafe { (self.chr_ctrl as *const ChrCtrl).as_ref() }
+    }
+
+    /// # Safety
+    /// The ChrCtrl belongs to the game, no other references to it may be
+    /// held while the returned one is alive.
+    #[allow(clippy::mut_from_ref)]
+    pub unsafe fn chr_ctrl_mut(&self) -> Option<&mut ChrCtrl> {
+        (self.chr_ctrl as *mut ChrCtrl).as_mut()
+    }
+
+    pub fn modules(&self) -> Option<&ChrInsModuleContainer<'a>> {
+        unsafe { self.module_container.as_ref() }
+    }
+
+    pub fn physics_module(&self) -> Option<&ChrPhysicsModule<'a>> {
+        unsafe { self.modules()?.physics.as_ref() }
+    }
+
+    /// Current position in the world. Characters that are still loading in
+    /// don't have their modules set up yet.
+    pub fn position(&self) -> Option<Vector4> {
+        self.physics_module().map(|physics| physics.position)
+    }
+}
+
+/// Holds the modules that make up a character's behavior. Only the physics
+/// module's slot is known, it comes from the crate's original ChrIns mapping.
+#[repr(C)]
+#[derive(FromBytes, FromZeroes)]
+pub struct ChrInsModuleContainer<'a> {
+    unk0: [u8; 0x68],
+    pub physics: *const ChrPhysicsModule<'a>,
 }
 
 #[repr(C)]
-pub struct ChrCtrl<'a> {
+#[derive(FromBytes, FromZeroes)]
+pub struct ChrPhysicsModule<'a> {
+    pub vftable: usize,
+    pub owner: *const ChrIns<'a>,
+    unk10: [u8; 0x60],
+    pub position: Vector4,
+}
+
+/// Drives the character's animations, physics and collisions. The owner is
+/// kept as an address, use the accessor to follow it.
+#[repr(C)]
+#[derive(FromZeroes)]
+pub struct ChrCtrl {
     pub vftable: usize,
     unk8: u64,
-    pub owner: &'a ChrIns<'a>,
-    pub manipulator: usize,
+    pub owner: usize,
+    /// Whatever is controlling the character, be it player input, the AI or
+    /// the network.
+    pub manipulator: *mut ChrManipulator,
     unk20: usize,
-    ragdoll_ins: usize,
-    chr_collision: usize,
-    unk38: [u8; 240],
-    pub chr_ragdoll_state: u8,
+    pub ragdoll_ins: *mut ChrRagdollIns,
+    pub chr_collision: *mut ChrCollision,
+    unk38: [u8; 0xf0],
+    /// See RagdollState.
+    ragdoll_state: u8,
+}
+
+/// Controls a character. Its layout hasn't been mapped, only ever handle it
+/// through pointers.
+#[repr(C)]
+pub struct ChrManipulator {
+    _opaque: [u8; 0],
+}
+
+/// A character's ragdoll. Its layout hasn't been mapped, only ever handle it
+/// through pointers.
+#[repr(C)]
+pub struct ChrRagdollIns {
+    _opaque: [u8; 0],
+}
+
+/// A character's collision. Its layout hasn't been mapped, only ever handle
+/// it through pointers.
+#[repr(C)]
+pub struct ChrCollision {
+    _opaque: [u8; 0],
+}
+
+impl ChrCtrl {
+    pub fn owner(&self) -> Option<&ChrIns<'_>> {
+        unsafe { (self.owner as *const ChrIns).as_ref() }
+    }
+
+    pub fn ragdoll_state(&self) -> RagdollState {
+        RagdollState::from(self.ragdoll_state)
+    }
+
+    pub fn set_ragdoll_state(&mut self, state: RagdollState) {
+        self.ragdoll_state = state.into();
+    }
+
+    /// Switches between animated and ragdolled. Characters in a state we
+    /// don't know about are made to ragdoll.
+    pub fn toggle_ragdoll(&mut self) {
+        let state = match self.ragdoll_state() {
+            RagdollState::Ragdoll => RagdollState::Animated,
+            _ => RagdollState::Ragdoll,
+        };
+
+        self.set_ragdoll_state(state);
+    }
+}
+
+/// The byte at ChrCtrl+0x128. The values come from the crate's original
+/// spectate code, which ragdolled characters by flipping it between 0 and 2.
+/// Nothing else has been seen in there yet.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RagdollState {
+    /// Character is driven by its animations.
+    Animated,
+    /// Character has gone limp and is driven by the ragdoll physics.
+    Ragdoll,
+    Unknown(u8),
+}
+
+impl From<u8> for RagdollState {
+    fn from(value: u8) -> Self {
+        match value {
+            0 => Self::Animated,
+            2 => Self::Ragdoll,
+            _ => Self::Unknown(value),
+        }
+    }
+}
+
+impl From<RagdollState> for u8 {
+    fn from(value: RagdollState) -> Self {
+        match value {
+            RagdollState::Animated => 0,
+            RagdollState::Ragdoll => 2,
+            RagdollState::Unknown(value) => value,
+        }
+    }
 }
@@ -0,0 +1,137 @@
+use std::collections::HashSet;
+
+use crate::game::cs::{ChrIns, ChrSet, FieldInsHandle, MapId, WorldChrMan};
+use crate::game::matrix::Vector4;
+
+/// Where WorldChrMan keeps track of a character.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ChrSetOrigin {
+    MainPlayer,
+    ChrSet1,
+    ChrSet2,
+    ChrSet3,
+    ChrSet4,
+    /// Either the OpenFieldChrSet itself or its first list.
+    OpenField,
+    /// ChrSet of the WorldBlockChr at the given index.
+    WorldBlock(usize),
+}
+
+#[derive(Clone, Copy)]
+pub struct ChrQueryEntry<'a> {
+    pub origin: ChrSetOrigin,
+    pub chr_ins: &'a ChrIns<'a>,
+}
+
+/// Filters for querying WorldChrMan for characters. Every filter that is set
+/// has to match for a character to be included.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct ChrQuery {
+    pub map_id: Option<MapId>,
+    pub origin: Option<ChrSetOrigin>,
+    pub max_distance: Option<f32>,
+}
+
+impl ChrQuery {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn map_id(mut self, map_id: MapId) -> Self {
+        self.map_id = Some(map_id);
+        self
+    }
+
+    pub fn origin(mut self, origin: ChrSetOrigin) -> Self {
+        self.origin = Some(origin);
+        self
+    }
+
+    /// Only includes characters within distance of the main player. Nothing
+    /// matches while there is no main player to measure from.
+    pub fn max_distance(mut self, distance: f32) -> Self {
+        self.max_distance = Some(distance);
+        self
+    }
+
+    fn matches(&self, entry: &ChrQueryEntry, player_position: Option<Vector4>) -> bool {
+        if self.map_id.is_some_and(|map_id| entry.chr_ins.map_id_1 != map_id) {
+            return false;
+        }
+
+        if self.origin.is_some_and(|origin| entry.origin != origin) {
+            return false;
+        }
+
+        if let Some(max_distance) = self.max_distance {
+            let distance = player_position
+                .zip(entry.chr_ins.position())
+                .map(|(player, position)| player.distance(&position));
+
+            if !distance.is_some_and(|distance| distance <= max_distance) {
+                return false;
+            }
+        }
+
+        true
+    }
+}
+
+impl<'a> WorldChrMan<'a> {
+    pub fn main_player(&self) -> Option<&ChrIns<'a>> {
+        unsafe { self.main_player.as_ref() }
+    }
+
+    /// Iterates over every live character. Characters tracked by multiple
+    /// ChrSets are only yielded once, for the first origin they were found
+    /// in.
+    pub fn characters(&self) -> impl Iterator<Item = ChrQueryEntry<'a>> + '_ {
+        let main_player = unsafe { self.main_player.as_ref() }
+            .map(|chr_ins| ChrQueryEntry { origin: ChrSetOrigin::MainPlayer, chr_ins });
+
+        let chr_sets = self.chr_sets()
+            .flat_map(|(origin, chr_set)| {
+                chr_set.character_iter()
+                    .map(move |element| ChrQueryEntry { origin, chr_ins: element.chr_ins })
+            });
+
+        let open_field_list = self.open_field_chr_set.list1_iter()
+            .filter_map(|(_, entry)| entry.chr_ins())
+            .map(|chr_ins| ChrQueryEntry { origin: ChrSetOrigin::OpenField, chr_ins });
+
+        let mut seen = HashSet::new();
+        main_player.into_iter()
+            .chain(chr_sets)
+            .chain(open_field_list)
+            .filter(move |entry| seen.insert(entry.chr_ins.field_ins_handle))
+    }
+
+    /// Iterates over the characters matching the query.
+    pub fn query(&self, query: ChrQuery) -> impl Iterator<Item = ChrQueryEntry<'a>> + '_ {
+        let player_position = self.main_player().and_then(ChrIns::position);
+
+        self.characters()
+            .filter(move |entry| query.matches(entry, player_position))
+    }
+
+    pub fn find_by_handle(&self, field_ins_handle: FieldInsHandle) -> Option<ChrQueryEntry<'a>> {
+        self.characters()
+            .find(|entry| entry.chr_ins.field_ins_handle == field_ins_handle)
+    }
+
+    fn chr_sets(&self) -> impl Iterator<Item = (ChrSetOrigin, &ChrSet<'a>)> {
+        let chr_sets = [
+            (ChrSetOrigin::ChrSet1, &self.chr_set_1),
+            (ChrSetOrigin::ChrSet2, &self.chr_set_2),
+            (ChrSetOrigin::ChrSet3, &self.chr_set_3),
+            (ChrSetOrigin::ChrSet4, &self.chr_set_4),
+            (ChrSetOrigin::OpenField, &self.open_field_chr_set.base),
+        ];
+
+        let world_blocks = self.world_block_chr.iter()
+            .enumerate()
+            .map(|(index, block)| (ChrSetOrigin::WorldBlock(index), &block.chr_set1));
+
+        chr_sets.into_iter().chain(world_blocks)
+    }
+}
@@ -1,6 +1,6 @@
 use std::ffi;
 
-use crate::game::dl::DLWString;
+use crate::game::dl::{DLAllocatorRef, DLStringError, DLWString};
 
 #[repr(C)]
 pub struct CSEzSelectBot {
@@ -10,7 +10,19 @@ pub struct CSEzSelectBot {
 
 #[repr(C)]
 pub struct CSEzSelectBotString {
-    pub allocator: *const ffi::c_void,
+    pub allocator: DLAllocatorRef,
     pub string: DLWString,
     pub unk28: u64,
 }
+
+impl CSEzSelectBotString {
+    /// Replaces the string using the allocator the string belongs to.
+    pub fn set(&mut self, value: &str) -> Result<(), DLStringError> {
+        // The game keeps the allocator around for as long as the string it
+        // allocated.
+        let allocator = unsafe { self.allocator.get() }
+            .ok_or(DLStringError::MissingAllocator)?;
+
+        self.string.set(value, allocator)
+    }
+}
@@ -0,0 +1,117 @@
+use std::fmt;
+use std::num::ParseIntError;
+use std::str::FromStr;
+
+use zerocopy::{FromBytes, FromZeroes};
+
+/// Identifies a field instance such as a character or an asset. The
+/// selector tells where the instance is stored, the instance id tells apart
+/// instances that have occupied the same spot over time.
+#[repr(C)]
+#[derive(FromBytes, FromZeroes, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
+pub struct FieldInsHandle {
+    pub instance_id: u32,
+    pub selector: FieldInsSelector,
+}
+
+impl FieldInsHandle {
+    pub const fn new(instance_id: u32, selector: FieldInsSelector) -> Self {
+        Self { instance_id, selector }
+    }
+}
+
+/// Formats as `{selector:08x}_{instance_id}`.
+impl fmt::Display for FieldInsHandle {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}_{}", self.selector, self.instance_id)
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum FieldInsHandleParseError {
+    MissingSeparator,
+    InvalidSelector(ParseIntError),
+    InvalidInstanceId(ParseIntError),
+}
+
+impl FromStr for FieldInsHandle {
+    type Err = FieldInsHandleParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (selector, instance_id) = s.split_once('_')
+            .ok_or(FieldInsHandleParseError::MissingSeparator)?;
+
+        Ok(Self {
+            instance_id: instance_id.parse()
+                .map_err(FieldInsHandleParseError::InvalidInstanceId)?,
+            selector: selector.parse()
+                .map_err(FieldInsHandleParseError::InvalidSelector)?,
+        })
+    }
+}
+
+/// Packed location of a field instance.
+///
+/// ```text
+/// 31      28 27            20 19                                  0
+/// +---------+----------------+------------------------------------+
+/// |  type   |   container    |               index                |
+/// +---------+----------------+------------------------------------+
+/// ```
+///
+/// The container is the index of the WorldBlockChr that holds the instance.
+#[repr(transparent)]
+#[derive(FromBytes, FromZeroes, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct FieldInsSelector(pub u32);
+
+impl FieldInsSelector {
+    const TYPE_SHIFT: u32 = 28;
+    const CONTAINER_SHIFT: u32 = 20;
+    const CONTAINER_MASK: u32 = 0xff;
+    const INDEX_MASK: u32 = 0xfffff;
+
+    /// Packs a selector. Parts that don't fit their bits are truncated.
+    pub const fn new(field_ins_type: u8, container: u8, index: u32) -> Self {
+        Self(
+            ((field_ins_type as u32 & 0xf) << Self::TYPE_SHIFT)
+                | ((container as u32) << Self::CONTAINER_SHIFT)
+                | (index & Self::INDEX_MASK),
+        )
+    }
+
+    pub const fn field_ins_type(&self) -> u8 {
+        (self.0 >> Self::TYPE_SHIFT) as u8
+    }
+
+    pub const fn container(&self) -> u8 {
+        ((self.0 >> Self::CONTAINER_SHIFT) & Self::CONTAINER_MASK) as u8
+    }
+
+    pub const fn index(&self) -> u32 {
+        self.0 & Self::INDEX_MASK
+    }
+}
+
+impl fmt::Display for FieldInsSelector {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:08x}", self.0)
+    }
+}
+
+impl fmt::Debug for FieldInsSelector {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("FieldInsSelector")
+            .field("field_ins_type", &self.field_ins_type())
+            .field("container", &self.container())
+            .field("index", &self.index())
+            .finish()
+    }
+}
+
+impl FromStr for FieldInsSelector {
+    type Err = ParseIntError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        u32::from_str_radix(s, 16).map(Self)
+    }
+}
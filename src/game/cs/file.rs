@@ -1,32 +1,34 @@
 use std::ffi;
 use zerocopy::{FromBytes, FromZeroes};
 
-use crate::game::dl::DLPlainLightMutex;
+use crate::game::dl::{DLFileOperatorVMT, DLPlainLightMutex, DLPlainLightMutexGuard, LockedIter};
 use crate::game::fd4::{
     FD4BasicHashString, FD4ResCap, FD4ResCapHolder
 };
+use crate::game::stl::OpaqueStdTree;
+use crate::util::singleton::DLRFLocatable;
 
 #[repr(C)]
 #[derive(FromBytes, FromZeroes)]
 pub struct CSFile<'a> {
-    pub vftable: usize,
+    pub vftable: DLFileOperatorVMT,
     pub file_repository_1: &'a CSFileRepository<'a>,
     // TODO: Incomplete..
 }
 
+impl DLRFLocatable for CSFile<'_> {
+    const DLRF_NAME: &'static str = "CSFile";
+}
+
 #[repr(C)]
-#[derive(FromBytes, FromZeroes)]
+#[derive(FromZeroes)]
 pub struct CSFileRepository<'a> {
     // TODO: This is actually embedding an FD4FileRepository of size 0x210
     pub repository_res_cap: FD4ResCap<'a, [u8; 0x10]>,
     pub holder1: FD4ResCapHolder<'a, ()>,
     pub holder2: FD4ResCapHolder<'a, ()>,
 
-    // Some type of btree?
-    pub unkc8_allocator: usize,
-    pub unkd0_tree_pointer: usize,
-    pub unkd8_tree_size: u32,
-    pub unkdc_tree_pad: u32,
+    pub unkc8_tree: OpaqueStdTree,
 
     pub mutexes: [&'a CSFileRepositoryMutex; 5],
     pub unk108: usize,
@@ -36,8 +38,27 @@ pub struct CSFileRepository<'a> {
     pub unk128: usize,
 }
 
+impl<'a> CSFileRepository<'a> {
+    /// The mutex the loader is assumed to hold while it's inserting into the
+    /// holders. Taking the first one is a guess, it hasn't been checked
+    /// against the loader's code or a capture yet.
+    pub fn loader_mutex(&self) -> &DLPlainLightMutex {
+        &self.mutexes[0].mutex
+    }
+
+    /// Locks the repository through the loader mutex.
+    pub fn lock(&self) -> DLPlainLightMutexGuard<'_> {
+        self.loader_mutex().lock()
+    }
+
+    /// Iterates over the FileCaps in both holders while holding the
+    /// repository's lock.
+    pub fn iter(&self) -> LockedIter<'_, impl Iterator<Item = &FD4ResCap<'_, ()>>> {
+        LockedIter::new(self.lock(), self.holder1.iter().chain(self.holder2.iter()))
+    }
+}
+
 #[repr(C)]
-#[derive(FromBytes, FromZeroes)]
 pub struct CSFileRepositoryMutex {
     pub mutex: DLPlainLightMutex,
     pub unk30: u32,
@@ -0,0 +1,147 @@
+use std::cmp::Ordering;
+use std::fmt;
+use std::num::ParseIntError;
+use std::str::FromStr;
+
+use zerocopy::{FromBytes, FromZeroes};
+
+/// Identifies a map, named like `m60_42_36_00` by the game. Stored as a
+/// little endian u32 so the area ends up in the most significant byte.
+///
+/// Open world maps (areas 60 and 61) are laid out on a grid. For those the
+/// block and region are the tile's x and z coordinates and the index is the
+/// tile's scale, where each scale step doubles the size of a tile.
+#[repr(C)]
+#[derive(FromBytes, FromZeroes, Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub struct MapId {
+    pub index: u8,
+    pub region: u8,
+    pub block: u8,
+    pub area: u8,
+}
+
+impl MapId {
+    /// Used by the game for things that aren't placed in any map.
+    pub const NONE: MapId = MapId::from_u32(u32::MAX);
+
+    pub const fn new(area: u8, block: u8, region: u8, index: u8) -> Self {
+        Self { index, region, block, area }
+    }
+
+    pub const fn from_u32(value: u32) -> Self {
+        let [index, region, block, area] = value.to_le_bytes();
+        Self { index, region, block, area }
+    }
+
+    pub const fn to_u32(self) -> u32 {
+        u32::from_le_bytes([self.index, self.region, self.block, self.area])
+    }
+
+    pub fn is_none(&self) -> bool {
+        *self == Self::NONE
+    }
+
+    /// Whether the map is a tile of the base game's or the DLC's open world.
+    pub fn is_open_world(&self) -> bool {
+        !self.is_none() && matches!(self.area, 60 | 61)
+    }
+
+    /// Whether the map is a legacy dungeon, which includes all of the
+    /// smaller dungeons as they aren't part of the grid either.
+    pub fn is_legacy_dungeon(&self) -> bool {
+        !self.is_none() && !self.is_open_world()
+    }
+
+    /// Returns the tiles surrounding an open world tile at the same scale,
+    /// starting north west and going row by row. Tiles beyond the edge of the
+    /// grid are left out. Legacy dungeons don't have any neighbours.
+    pub fn neighbours(&self) -> Vec<MapId> {
+        if !self.is_open_world() {
+            return Vec::new();
+        }
+
+        let mut result = Vec::with_capacity(8);
+        for z in [1, 0, -1] {
+            for x in [-1, 0, 1] {
+                if x == 0 && z == 0 {
+                    continue;
+                }
+
+                let block = self.block.checked_add_signed(x);
+                let region = self.region.checked_add_signed(z);
+                if let (Some(block), Some(region)) = (block, region) {
+                    result.push(MapId { block, region, ..*self });
+                }
+            }
+        }
+
+        result
+    }
+}
+
+impl From<u32> for MapId {
+    fn from(value: u32) -> Self {
+        Self::from_u32(value)
+    }
+}
+
+impl From<MapId> for u32 {
+    fn from(value: MapId) -> Self {
+        value.to_u32()
+    }
+}
+
+impl PartialOrd for MapId {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for MapId {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.to_u32().cmp(&other.to_u32())
+    }
+}
+
+impl fmt::Display for MapId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.is_none() {
+            return f.write_str("none");
+        }
+
+        write!(f, "m{:02}_{:02}_{:02}_{:02}", self.area, self.block, self.region, self.index)
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum MapIdParseError {
+    InvalidFormat,
+    InvalidNumber(ParseIntError),
+}
+
+impl FromStr for MapId {
+    type Err = MapIdParseError;
+
+    /// Parses names like `m60_42_36_00`. The trailing parts may be left
+    /// out, in which case they're zero.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s == "none" {
+            return Ok(Self::NONE);
+        }
+
+        let s = s.strip_prefix('m')
+            .ok_or(MapIdParseError::InvalidFormat)?;
+
+        let mut parts = [0u8; 4];
+        for (index, part) in s.split('_').enumerate() {
+            if index == parts.len() || part.is_empty() {
+                return Err(MapIdParseError::InvalidFormat);
+            }
+
+            parts[index] = part.parse().map_err(MapIdParseError::InvalidNumber)?;
+        }
+
+        let [area, block, region, index] = parts;
+        Ok(Self::new(area, block, region, index))
+    }
+}
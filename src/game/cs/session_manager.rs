@@ -1,8 +1,10 @@
 use zerocopy::{FromBytes, FromZeroes};
 
+use crate::game::stl::StdVector;
 use crate::util::singleton::DLRFLocatable;
 
 #[repr(u32)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum WorldState {
     // Nothing is happening
     Offline = 0x0,
@@ -23,6 +25,7 @@ pub enum WorldState {
 }
 
 #[repr(u32)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum ProtocolState {
     // Nothing is happening
     Inactive = 0x0,
@@ -35,13 +38,94 @@ pub enum ProtocolState {
     Unk7 = 0x7,
 }
 
+/// Hands back the raw value if it doesn't match any known state.
+impl TryFrom<u32> for WorldState {
+    type Error = u32;
+
+    fn try_from(value: u32) -> Result<Self, Self::Error> {
+        Ok(match value {
+            0x0 => Self::Offline,
+            0x1 => Self::CreatingLobby,
+            0x2 => Self::Unk2,
+            0x3 => Self::Host,
+            0x4 => Self::AwaitingJoin,
+            0x5 => Self::Unk5,
+            0x6 => Self::Client,
+            0x7 => Self::ClosingLobby,
+            _ => return Err(value),
+        })
+    }
+}
+
+/// Hands back the raw value if it doesn't match any known state.
+impl TryFrom<u32> for ProtocolState {
+    type Error = u32;
+
+    fn try_from(value: u32) -> Result<Self, Self::Error> {
+        Ok(match value {
+            0x0 => Self::Inactive,
+            0x1 => Self::AwaitingWorldData,
+            0x2 => Self::Unk2,
+            0x3 => Self::Unk3,
+            0x4 => Self::Unk4,
+            0x5 => Self::Unk5,
+            0x6 => Self::InWorld,
+            0x7 => Self::Unk7,
+            _ => return Err(value),
+        })
+    }
+}
+
 #[repr(C)]
-#[derive(FromZeroes, FromBytes)]
+#[derive(FromZeroes)]
 pub struct CSSessionManager {
     pub vftable: usize,
     pub unk0x8: u32,
     pub world_state: u32,
     pub protocol_state: u32,
+    unk14: u32,
+    unk18: [u8; 0xb8],
+    /// Players in the session, which entry is ours isn't known. The offset
+    /// and the entry size haven't been checked against a capture yet.
+    pub players: StdVector<CSSessionManagerPlayerEntry>,
+    /// Our own entry, filled in regardless of being in a session or not.
+    pub local_player: CSSessionManagerPlayerEntry,
+}
+
+impl CSSessionManager {
+    pub fn world_state(&self) -> Result<WorldState, u32> {
+        WorldState::try_from(self.world_state)
+    }
+
+    pub fn protocol_state(&self) -> Result<ProtocolState, u32> {
+        ProtocolState::try_from(self.protocol_state)
+    }
+
+    /// No session gets anywhere near this many players, a longer list means
+    /// we're not looking at the player list.
+    const PLAYER_LIMIT: usize = 16;
+
+    /// The players in the session. As the layout hasn't been checked
+    /// against a capture the list is sanity checked first, None if it doesn't
+    /// look like a list of players.
+    pub fn players(&self) -> Option<&[CSSessionManagerPlayerEntry]> {
+        if !self.players.is_well_formed() || self.players.len() > Self::PLAYER_LIMIT {
+            return None;
+        }
+
+        Some(self.players.as_slice())
+    }
+}
+
+#[repr(C)]
+#[derive(FromZeroes, FromBytes)]
+pub struct CSSessionManagerPlayerEntry {
+    pub steam_id: u64,
+    unk8: u64,
+    /// Slot the player occupies in the session.
+    pub session_slot: i32,
+    unk14: u32,
+    unk18: [u8; 0x10],
 }
 
 impl DLRFLocatable for CSSessionManager {
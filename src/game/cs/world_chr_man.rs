@@ -1,9 +1,13 @@
 use std::ffi;
+use std::iter;
+use std::slice;
 use std::marker::PhantomData;
 
 use zerocopy::{FromBytes, FromZeroes, Unalign};
 
-use crate::game::cs::ChrIns;
+use crate::game::cs::{ChrIns, FieldInsHandle, MapId};
+use crate::game::dl::DLAllocatorRef;
+use crate::game::stl::OpaqueStdTree;
 use crate::util::singleton::DLRFLocatable;
 
 #[repr(C)]
@@ -39,13 +43,25 @@ pub struct WorldChrMan<'a> {
     pub open_field_chr_set: OpenFieldChrSet<'a>,
 
     pub unk1cc58: [u8; 0x18b0],
-    pub main_player: &'a mut ChrIns<'a>,
+    pub main_player: *mut ChrIns<'a>,
 }
 
 impl DLRFLocatable for WorldChrMan<'_> {
     const DLRF_NAME: &'static str = "WorldChrMan";
 }
 
+impl<'a> WorldChrMan<'a> {
+    /// Looks up the WorldBlockChr holding a field instance.
+    pub fn world_block_chr_for(&self, handle: FieldInsHandle) -> Option<&WorldBlockChr<'a>> {
+        self.world_block_chr.get(handle.selector.container() as usize)
+    }
+
+    /// Resolves the map a field instance was loaded in for.
+    pub fn map_id_for(&self, handle: FieldInsHandle) -> Option<MapId> {
+        self.world_block_chr_for(handle).map(|world_block_chr| world_block_chr.map_id)
+    }
+}
+
 #[repr(C)]
 #[derive(FromZeroes, FromBytes)]
 pub struct WorldAreaChr<'a> {
@@ -73,64 +89,79 @@ pub struct WorldBlockChr<'a> {
     unkd0: [u8; 0x40],
     pub world_block_info2: usize,
     pub chr_set_ptr2: &'a mut ChrSet<'a>,
-    pub allocator: usize,
+    pub allocator: DLAllocatorRef,
     unk128: [u8; 0x30],
-    map_id: MapId,
+    pub map_id: MapId,
     unk15c: u32,
 }
 
 #[repr(C)]
-#[derive(FromZeroes, FromBytes)]
+#[derive(FromZeroes)]
 pub struct ChrSet<'a> {
     pub vftable: usize,
     unk8: i32,
     unkc: i32,
     pub capacity: i32,
     unk14: u32,
-    entries: *const ChrSetEntry<'a>,
+    pub entries: *const ChrSetEntry<'a>,
     pub count: i32,
     unk24: u32,
-    list1: UnkBtree,
-    list2: UnkBtree,
+    pub list1: OpaqueStdTree,
+    pub list2: OpaqueStdTree,
 }
 
 impl<'a> ChrSet<'a> {
-    pub fn character_iter(&'a self) -> impl Iterator<Item = ChrSetIterElement> {
+    /// Returns all entries, occupied or not.
+    pub fn entries(&self) -> &[ChrSetEntry<'a>] {
+        if self.entries.is_null() || self.capacity <= 0 {
+            return &[];
+        }
+
+        unsafe { slice::from_raw_parts(self.entries, self.capacity as usize) }
+    }
+
+    /// Iterates over the characters in the set. Empty entries are skipped
+    /// and iteration stops once count characters have been seen.
+    pub fn character_iter(&self) -> ChrSetIter<'_, 'a> {
         ChrSetIter {
-            remaining: self.count as usize,
-            current: self.entries,
+            entries: self.entries().iter().enumerate(),
+            remaining: self.count.max(0) as usize,
         }
     }
 }
 
-pub struct ChrSetIter<'a> {
+pub struct ChrSetIter<'s, 'a> {
+    entries: iter::Enumerate<slice::Iter<'s, ChrSetEntry<'a>>>,
     remaining: usize,
-    current: *const ChrSetEntry<'a>,
 }
 
-impl<'a> Iterator for ChrSetIter<'a> {
+impl<'a> Iterator for ChrSetIter<'_, 'a> {
     type Item = ChrSetIterElement<'a>;
 
     fn next(&mut self) -> Option<Self::Item> {
-        unsafe {
-            loop {
-                let chr_ins = (*self.current).chr_ins.as_mut();
-
-                self.current = self.current.wrapping_add(1);
-                self.remaining -= 1;
-
-                if let Some(chr_ins) = chr_ins {
-                    return Some(ChrSetIterElement { chr_ins });
-                }
-            }
+        if self.remaining == 0 {
+            return None;
         }
 
-        None
+        let (index, entry, chr_ins) = self.entries
+            .find_map(|(index, entry)| Some((index, entry, entry.chr_ins()?)))?;
+
+        self.remaining -= 1;
+        Some(ChrSetIterElement {
+            index,
+            chr_ins,
+            unk8: entry.unk8,
+            unkc: entry.unkc,
+        })
     }
 }
 
 pub struct ChrSetIterElement<'a> {
-    pub chr_ins: &'a mut ChrIns<'a>,
+    /// Index of the entry in the ChrSet.
+    pub index: usize,
+    pub chr_ins: &'a ChrIns<'a>,
+    pub unk8: u32,
+    pub unkc: u32,
 }
 
 #[repr(C)]
@@ -141,28 +172,56 @@ pub struct ChrSetEntry<'a> {
     pub unkc: u32,
 }
 
+impl<'a> ChrSetEntry<'a> {
+    pub fn chr_ins(&self) -> Option<&'a ChrIns<'a>> {
+        unsafe { self.chr_ins.as_ref() }
+    }
+}
+
 #[repr(C)]
-#[derive(FromZeroes, FromBytes)]
+#[derive(FromZeroes)]
 pub struct OpenFieldChrSet<'a> {
     pub base: ChrSet<'a>,
-    unk58: UnkBtree,
+    pub unk58: OpaqueStdTree,
     unk70: f32,
     pad74: u32,
-    list1: [OpenFieldChrSetList1Entry<'a>; 1500],
+    pub list1: [OpenFieldChrSetList1Entry<'a>; 1500],
     unk5e38: u32,
     unk5e3c: u32,
     unk5e40: u32,
     unk5e44: u32,
-    list2: [OpenFieldChrSetList2Entry; 1500],
+    pub list2: [OpenFieldChrSetList2Entry; 1500],
     unkbc08: u64,
     unkbc10: u64,
 }
 
+impl<'a> OpenFieldChrSet<'a> {
+    /// Iterates over the occupied entries of list1 along with their index.
+    pub fn list1_iter(&self) -> impl Iterator<Item = (usize, &OpenFieldChrSetList1Entry<'a>)> {
+        self.list1.iter()
+            .enumerate()
+            .filter(|(_, entry)| !entry.chr_ins.is_null())
+    }
+
+    /// Iterates over the non-empty entries of list2 along with their index.
+    pub fn list2_iter(&self) -> impl Iterator<Item = (usize, &OpenFieldChrSetList2Entry)> {
+        self.list2.iter()
+            .enumerate()
+            .filter(|(_, entry)| !entry.is_empty())
+    }
+}
+
 #[repr(C)]
 #[derive(FromZeroes, FromBytes)]
 pub struct OpenFieldChrSetList1Entry<'a> {
     pub unk0: u64,
-    pub chr_ins: &'a mut ChrIns<'a>,
+    pub chr_ins: *mut ChrIns<'a>,
+}
+
+impl<'a> OpenFieldChrSetList1Entry<'a> {
+    pub fn chr_ins(&self) -> Option<&'a ChrIns<'a>> {
+        unsafe { self.chr_ins.as_ref() }
+    }
 }
 
 #[repr(C)]
@@ -173,13 +232,10 @@ pub struct OpenFieldChrSetList2Entry {
     pub unkc: u32,
 }
 
-#[repr(C)]
-#[derive(FromZeroes, FromBytes)]
-pub struct UnkBtree {
-    pub vftable: usize,
-    pub head: usize,
-    pub entry_count: u32,
-    padding: u32,
+impl OpenFieldChrSetList2Entry {
+    pub fn is_empty(&self) -> bool {
+        self.unk0 == 0 && self.unk8 == 0 && self.unkc == 0
+    }
 }
 
 #[repr(C)]
@@ -187,17 +243,8 @@ pub struct UnkBtree {
 pub struct WorldGridAreaChr {
     pub base: WorldAreaChrBase,
     pub world_grid_area_info: usize,
-    pub allocator: usize,
+    pub allocator: DLAllocatorRef,
     pub head: usize,
     pub capacity: u32,
     pub size: u32,
 }
-
-#[repr(C)]
-#[derive(FromZeroes, FromBytes)]
-pub struct MapId {
-    pub index: u8,
-    pub region: u8,
-    pub block: u8,
-    pub area: u8,
-}
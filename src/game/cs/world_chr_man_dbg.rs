@@ -6,10 +6,26 @@ use crate::util::singleton::DLRFLocatable;
 #[repr(C)]
 #[derive(FromBytes, FromZeroes)]
 pub struct WorldChrManDbg<'a> {
-    pub unk0: [u8; 0xa8],
+    pub vftable: usize,
+    pub unk8: [u8; 0xa0],
     pub manipulator: usize,
     pub player_session_holder: usize,
-    pub cam_override_chr_ins: &'a mut ChrIns<'a>,
+    /// Character the camera follows instead of the main player.
+    pub cam_override_chr_ins: *mut ChrIns<'a>,
+}
+
+impl<'a> WorldChrManDbg<'a> {
+    pub fn cam_override(&self) -> Option<&ChrIns<'a>> {
+        unsafe { self.cam_override_chr_ins.as_ref() }
+    }
+
+    /// Points the camera at a character, None hands it back to the main
+    /// player.
+    pub fn set_cam_override(&mut self, chr_ins: Option<&ChrIns>) {
+        self.cam_override_chr_ins = chr_ins
+            .map(|chr_ins| (chr_ins as *const ChrIns).cast_mut().cast())
+            .unwrap_or(std::ptr::null_mut());
+    }
 }
 
 impl DLRFLocatable for WorldChrManDbg<'_> {
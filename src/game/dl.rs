@@ -1,7 +1,11 @@
+mod allocator;
 mod mutex;
 mod string;
 mod runtime_class;
+mod file_operator;
 
+pub use allocator::*;
 pub use mutex::*;
 pub use string::*;
 pub use runtime_class::*;
+pub use file_operator::*;
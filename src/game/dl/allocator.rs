@@ -0,0 +1,139 @@
+use std::alloc::Layout;
+use std::ptr::NonNull;
+
+use allocator_api2::alloc::{AllocError, Allocator};
+use zerocopy::{FromBytes, FromZeroes};
+
+/// Source of memory that the game is able to free again. Game structures that
+/// own heap memory carry a pointer to the allocator that it came from and
+/// will release it through that allocator once they're destroyed. Anything
+/// we hand to the game must therefore come from one of its allocators.
+///
+/// # Safety
+/// Implementations must return memory that can be released by calling
+/// deallocate on the same allocator, and as_raw must yield the address the
+/// game expects in the allocator fields of its structures.
+pub unsafe trait GameAllocator {
+    /// Allocates size bytes with at least the requested alignment. Returns
+    /// null if the allocation failed.
+    fn allocate(&self, size: usize, alignment: usize) -> *mut u8;
+
+    /// Releases memory previously obtained from allocate.
+    ///
+    /// # Safety
+    /// The pointer must have been returned by this allocator and must not be
+    /// used after this call.
+    unsafe fn deallocate(&self, ptr: *mut u8);
+
+    /// Address of the allocator as stored in game structures.
+    fn as_raw(&self) -> usize;
+}
+
+crate::vtable! {
+    pub struct DLAllocatorVMT for DLAllocator {
+        0 => unsafe fn destructor(&self, free: bool);
+        1 => fn allocator_id(&self) -> u32;
+        3 => fn heap_flags(&self, flags: *mut u64) -> *const u64;
+        4 => fn heap_capacity(&self) -> usize;
+        5 => fn heap_size(&self) -> usize;
+        6 => fn backing_heap_capacity(&self) -> usize;
+        7 => fn heap_allocation_count(&self) -> usize;
+        /// Size of an allocation previously made by this allocator.
+        8 => fn allocation_size(&self, ptr: *const u8) -> usize;
+        9 => fn allocate(&self, size: usize) -> *mut u8;
+        10 => fn allocate_aligned(&self, size: usize, alignment: usize) -> *mut u8;
+        /// Resizes an allocation, potentially moving it. Returns null if the
+        /// allocation couldn't be resized in which case the original
+        /// allocation is left untouched. The pointer must have been allocated
+        /// by this allocator.
+        11 => unsafe fn reallocate(&self, ptr: *mut u8, size: usize) -> *mut u8;
+        12 => unsafe fn reallocate_aligned(&self, ptr: *mut u8, size: usize, alignment: usize) -> *mut u8;
+        13 => unsafe fn deallocate(&self, ptr: *mut u8);
+    }
+}
+
+/// Base of the game's allocators. Every allocator is used through its vftable
+/// so we don't need to know anything about the specific implementation.
+#[repr(C)]
+pub struct DLAllocator {
+    pub vftable: DLAllocatorVMT,
+}
+
+unsafe impl GameAllocator for DLAllocator {
+    fn allocate(&self, size: usize, alignment: usize) -> *mut u8 {
+        self.allocate_aligned(size, alignment)
+    }
+
+    unsafe fn deallocate(&self, ptr: *mut u8) {
+        DLAllocator::deallocate(self, ptr)
+    }
+
+    fn as_raw(&self) -> usize {
+        self as *const _ as usize
+    }
+}
+
+/// Lets Rust collections allocate from the game's heaps. Collections built
+/// this way can be handed over to the game which will free them with the
+/// same allocator.
+unsafe impl Allocator for DLAllocator {
+    fn allocate(&self, layout: Layout) -> Result<NonNull<[u8]>, AllocError> {
+        let ptr = GameAllocator::allocate(self, layout.size(), layout.align());
+        let ptr = NonNull::new(ptr).ok_or(AllocError)?;
+
+        Ok(NonNull::slice_from_raw_parts(ptr, layout.size()))
+    }
+
+    unsafe fn deallocate(&self, ptr: NonNull<u8>, _layout: Layout) {
+        GameAllocator::deallocate(self, ptr.as_ptr())
+    }
+
+    unsafe fn grow(
+        &self,
+        ptr: NonNull<u8>,
+        _old_layout: Layout,
+        new_layout: Layout,
+    ) -> Result<NonNull<[u8]>, AllocError> {
+        let ptr = self.reallocate_aligned(ptr.as_ptr(), new_layout.size(), new_layout.align());
+        let ptr = NonNull::new(ptr).ok_or(AllocError)?;
+
+        Ok(NonNull::slice_from_raw_parts(ptr, new_layout.size()))
+    }
+
+    unsafe fn shrink(
+        &self,
+        ptr: NonNull<u8>,
+        old_layout: Layout,
+        new_layout: Layout,
+    ) -> Result<NonNull<[u8]>, AllocError> {
+        self.grow(ptr, old_layout, new_layout)
+    }
+}
+
+/// Allocator pointer as found in game structures. Might be null for
+/// structures that are embedded in static memory or never allocate.
+#[repr(transparent)]
+#[derive(FromZeroes, FromBytes, Clone, Copy, Default, PartialEq, Eq)]
+pub struct DLAllocatorRef(usize);
+
+impl DLAllocatorRef {
+    pub fn new(allocator: &impl GameAllocator) -> Self {
+        Self(allocator.as_raw())
+    }
+
+    pub fn as_raw(&self) -> usize {
+        self.0
+    }
+
+    pub fn is_null(&self) -> bool {
+        self.0 == 0
+    }
+
+    /// # Safety
+    /// The address must be null or point to a DLAllocator that outlives the
+    /// returned reference. Refs read from game memory next to the data the
+    /// allocator owns satisfy this for as long as that data is alive.
+    pub unsafe fn get(&self) -> Option<&DLAllocator> {
+        unsafe { (self.0 as *const DLAllocator).as_ref() }
+    }
+}
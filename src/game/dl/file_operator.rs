@@ -1,23 +1,32 @@
+use std::ffi;
+
+use crate::game::cs::CSFile;
+use crate::game::dl::DLRuntimeClass;
+use crate::game::fd4::{FD4BasicHashString, FD4ResCap};
+
 // Vftable buildup:
 //  - 0 = DLFileOperator::~DLFileOperator
 //  - 9 = DLFileOperator::CreateFile
 //  - 10 = DLFileOperator::Open
-#[repr(C)]
-pub struct DLFileOperatorVMT {
-    // Returns instance of DLRuntimeClass describing current class
-    pub get_runtime_class: fn(*const CSFile) -> *const ffi::c_void,
+// This disagrees with slots 0 and 1 below, both predate the vtable! macro
+// and neither has been settled yet.
+crate::vtable! {
+    pub struct DLFileOperatorVMT for CSFile<'_> {
+        /// Returns instance of DLRuntimeClass describing current class
+        0 => fn get_runtime_class(&self) -> *const DLRuntimeClass;
 
-    // Destructor
-    pub destructor: fn(*const CSFile, u64) -> *const ffi::c_void,
+        /// Destructor
+        1 => unsafe fn destructor(&self, flags: u64) -> *const ffi::c_void;
 
-    // Retrieves a FileCap from the first CSFileRepository
-    pub get_repository_1_resource: fn(*const CSFile, *const FD4BasicHashString) -> *const ffi::c_void,
+        /// Retrieves a FileCap from the first CSFileRepository
+        2 => fn get_repository_1_resource(&self, name: *const FD4BasicHashString) -> *const ffi::c_void;
 
-    // Seems to be inserting something?
-    pub unk3: fn(*const CSFile, *const FD4BasicHashString, *const FD4ResCap<()>, u32),
+        /// Seems to be inserting something?
+        3 => unsafe fn unk3(&self, name: *const FD4BasicHashString, res_cap: *const FD4ResCap<'static, ()>, unk: u32);
 
-    // Seems to be inserting something as well?
-    // pub unk4: fn(*const CSFile, *const FD4BasicHashString, *const FD4ResCap<()>, u32),
+        // Seems to be inserting something as well?
+        // 4 => unsafe fn unk4(&self, name: *const FD4BasicHashString, res_cap: *const FD4ResCap<'static, ()>, unk: u32);
+    }
 }
 
 // Constructor: 0x141f49730 
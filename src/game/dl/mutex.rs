@@ -1,11 +1,107 @@
-use std::ffi;
+use std::cell::UnsafeCell;
+use std::marker::PhantomData;
 
 use windows::Win32::System::Threading::CRITICAL_SECTION;
-use zerocopy::{FromBytes, FromZeroes};
 
 #[repr(C)]
-#[derive(FromBytes, FromZeroes)]
 pub struct DLPlainLightMutex {
     pub vftable: usize,
-    pub critical_section: [u8; 0x28],
+    /// Written to by whoever enters or leaves the critical section, which
+    /// happens through shared references.
+    pub critical_section: UnsafeCell<[u8; 0x28]>,
+}
+
+impl DLPlainLightMutex {
+    /// Enters the critical section, blocking until the game releases it. The
+    /// critical section is recursive so locking it again from the same
+    /// thread won't deadlock.
+    pub fn lock(&self) -> DLPlainLightMutexGuard<'_> {
+        unsafe { backend::enter(self.raw()) };
+        DLPlainLightMutexGuard { mutex: self, _not_send: PhantomData }
+    }
+
+    /// Enters the critical section only if no other thread holds it.
+    pub fn try_lock(&self) -> Option<DLPlainLightMutexGuard<'_>> {
+        if !unsafe { backend::try_enter(self.raw()) } {
+            return None;
+        }
+
+        Some(DLPlainLightMutexGuard { mutex: self, _not_send: PhantomData })
+    }
+
+    fn raw(&self) -> *mut CRITICAL_SECTION {
+        self.critical_section.get().cast()
+    }
+}
+
+/// Leaves the critical section once dropped. Critical sections have to be
+/// left by the thread that entered them so the guard can't be sent.
+pub struct DLPlainLightMutexGuard<'a> {
+    mutex: &'a DLPlainLightMutex,
+    _not_send: PhantomData<*const ()>,
+}
+
+impl Drop for DLPlainLightMutexGuard<'_> {
+    fn drop(&mut self) {
+        unsafe { backend::leave(self.mutex.raw()) };
+    }
+}
+
+/// Iterator that keeps a mutex locked until it's dropped.
+pub struct LockedIter<'a, I> {
+    inner: I,
+    _guard: DLPlainLightMutexGuard<'a>,
+}
+
+impl<'a, I: Iterator> LockedIter<'a, I> {
+    pub fn new(guard: DLPlainLightMutexGuard<'a>, inner: I) -> Self {
+        Self { inner, _guard: guard }
+    }
+}
+
+impl<I: Iterator> Iterator for LockedIter<'_, I> {
+    type Item = I::Item;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.inner.next()
+    }
+}
+
+#[cfg(not(test))]
+mod backend {
+    use windows::Win32::System::Threading::{
+        EnterCriticalSection, LeaveCriticalSection, TryEnterCriticalSection, CRITICAL_SECTION,
+    };
+
+    pub unsafe fn enter(critical_section: *mut CRITICAL_SECTION) {
+        EnterCriticalSection(critical_section)
+    }
+
+    pub unsafe fn try_enter(critical_section: *mut CRITICAL_SECTION) -> bool {
+        TryEnterCriticalSection(critical_section).as_bool()
+    }
+
+    pub unsafe fn leave(critical_section: *mut CRITICAL_SECTION) {
+        LeaveCriticalSection(critical_section)
+    }
+}
+
+// Tests don't run inside of the game so there is nobody to race with. Only
+// the recursion count is maintained so tests can tell if a lock is held.
+#[cfg(test)]
+mod backend {
+    use windows::Win32::System::Threading::CRITICAL_SECTION;
+
+    pub unsafe fn enter(critical_section: *mut CRITICAL_SECTION) {
+        (*critical_section).RecursionCount += 1;
+    }
+
+    pub unsafe fn try_enter(critical_section: *mut CRITICAL_SECTION) -> bool {
+        enter(critical_section);
+        true
+    }
+
+    pub unsafe fn leave(critical_section: *mut CRITICAL_SECTION) {
+        (*critical_section).RecursionCount -= 1;
+    }
 }
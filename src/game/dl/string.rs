@@ -1,11 +1,19 @@
-use std::ffi;
+use std::fmt;
+use std::marker;
 use std::mem;
+use std::ptr;
 use std::slice;
-use std::marker;
+use std::string;
 
 use zerocopy::FromBytes;
 use zerocopy::FromZeroes;
 
+use crate::game::dl::GameAllocator;
+
+/// MSVC's std::basic_string minus the allocator, which is stored by whatever
+/// embeds the string. Strings that fit in the 16 byte buffer are stored
+/// inline, anything longer is stored on the heap with the buffer holding
+/// the pointer to it. The capacity tells which of the two is in use.
 #[repr(C)]
 #[derive(FromZeroes, FromBytes)]
 pub struct DLBasicString<T> {
@@ -16,46 +24,205 @@ pub struct DLBasicString<T> {
 }
 
 pub type DLWString = DLBasicString<u16>;
+pub type DLString = DLBasicString<u8>;
 
-impl ToString for DLWString {
-    fn to_string(&self) -> String {
-        let slice_size = self.length * mem::size_of::<u16>();
-        let bytes = if slice_size >= 16 {
-            let ptr = usize::from_le_bytes(
-                self.union[0..8].try_into().unwrap()
-            );
+#[derive(Debug)]
+pub enum DLStringError {
+    InvalidUtf8(string::FromUtf8Error),
+    InvalidUtf16(string::FromUtf16Error),
+    AllocationFailed,
+    MissingAllocator,
+}
 
-            unsafe {
-                slice::from_raw_parts(ptr as *const u16, self.length).to_vec()
-            }
-        } else {
-            self.union[0..slice_size]
-                .chunks_exact(2)
-                .map(|c| ((c[1] as u16) << 8) | c[0] as u16)
-                .collect::<Vec<u16>>()
-        };
+/// Code units that a DLBasicString can be made of.
+pub trait DLStringUnit: Copy + PartialEq + 'static {
+    fn encode(value: &str) -> Vec<Self>;
+    fn decode(units: &[Self]) -> Result<String, DLStringError>;
+    fn decode_lossy(units: &[Self]) -> String;
+}
+
+impl DLStringUnit for u8 {
+    fn encode(value: &str) -> Vec<Self> {
+        value.as_bytes().to_vec()
+    }
+
+    fn decode(units: &[Self]) -> Result<String, DLStringError> {
+        String::from_utf8(units.to_vec())
+            .map_err(DLStringError::InvalidUtf8)
+    }
 
-        String::from_utf16_lossy(bytes.as_slice())
+    fn decode_lossy(units: &[Self]) -> String {
+        String::from_utf8_lossy(units).to_string()
     }
 }
 
-pub type DLString = DLBasicString<u8>;
+impl DLStringUnit for u16 {
+    fn encode(value: &str) -> Vec<Self> {
+        value.encode_utf16().collect()
+    }
 
-impl ToString for DLString {
-    fn to_string(&self) -> String {
-        let bytes = if self.length >= 16 {
-            let ptr = usize::from_le_bytes(
-                self.union[0..8].try_into().unwrap()
+    fn decode(units: &[Self]) -> Result<String, DLStringError> {
+        String::from_utf16(units)
+            .map_err(DLStringError::InvalidUtf16)
+    }
+
+    fn decode_lossy(units: &[Self]) -> String {
+        String::from_utf16_lossy(units)
+    }
+}
+
+impl<T: DLStringUnit> DLBasicString<T> {
+    /// Amount of code units that fit in the inline buffer, including the null
+    /// terminator.
+    const INLINE_CAPACITY: usize = 16 / mem::size_of::<T>();
+
+    /// Creates an empty string using the inline buffer.
+    pub fn new() -> Self {
+        let mut result = Self::new_zeroed();
+        result.capacity = Self::INLINE_CAPACITY - 1;
+        result
+    }
+
+    /// Creates a string holding value. Only allocates if the value doesn't
+    /// fit the inline buffer.
+    pub fn from_str_in(
+        value: &str,
+        allocator: &impl GameAllocator,
+    ) -> Result<Self, DLStringError> {
+        let mut result = Self::new();
+        result.set(value, allocator)?;
+        Ok(result)
+    }
+
+    /// Creates a string if value fits in the inline buffer.
+    pub fn from_str_inline(value: &str) -> Option<Self> {
+        let units = T::encode(value);
+        if units.len() >= Self::INLINE_CAPACITY {
+            return None;
+        }
+
+        let mut result = Self::new();
+        unsafe { result.write(&units) };
+        Some(result)
+    }
+
+    pub fn is_inline(&self) -> bool {
+        self.capacity < Self::INLINE_CAPACITY
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.length == 0
+    }
+
+    /// Replaces the contents of the string. Reuses the current heap buffer if
+    /// it's big enough, otherwise a new buffer is allocated and the old one
+    /// is released. Allocator must be the one this string belongs to.
+    pub fn set(
+        &mut self,
+        value: &str,
+        allocator: &impl GameAllocator,
+    ) -> Result<(), DLStringError> {
+        let units = T::encode(value);
+
+        // Zeroed strings are inline strings that haven't been given their
+        // capacity yet.
+        self.capacity = self.capacity.max(Self::INLINE_CAPACITY - 1);
+
+        if units.len() >= Self::INLINE_CAPACITY && units.len() > self.capacity {
+            let buffer = allocator.allocate(
+                (units.len() + 1) * mem::size_of::<T>(),
+                mem::align_of::<T>(),
             );
 
-            unsafe {
-                slice::from_raw_parts(ptr as *const u8, self.length).to_vec()
+            if buffer.is_null() {
+                return Err(DLStringError::AllocationFailed);
             }
+
+            self.free(allocator);
+            self.union[0..8].copy_from_slice(&(buffer as usize).to_le_bytes());
+            self.capacity = units.len();
+        }
+
+        unsafe { self.write(&units) };
+        Ok(())
+    }
+
+    /// Releases the heap buffer if there is one and resets the string to an
+    /// empty inline string.
+    pub fn free(&mut self, allocator: &impl GameAllocator) {
+        if !self.is_inline() {
+            unsafe { allocator.deallocate(self.data_ptr() as *mut u8) };
+        }
+
+        self.union = [0; 16];
+        self.length = 0;
+        self.capacity = Self::INLINE_CAPACITY - 1;
+    }
+
+    pub fn as_slice(&self) -> &[T] {
+        if self.length == 0 {
+            return &[];
+        }
+
+        unsafe { slice::from_raw_parts(self.data_ptr(), self.length) }
+    }
+
+    /// Decodes the string, failing on malformed contents instead of
+    /// substituting replacement characters.
+    pub fn try_to_string(&self) -> Result<String, DLStringError> {
+        T::decode(self.as_slice())
+    }
+
+    fn data_ptr(&self) -> *const T {
+        if self.is_inline() {
+            self.union.as_ptr() as *const T
         } else {
-            self.union[0..self.length].to_vec()
-        };
+            usize::from_le_bytes(self.union[0..8].try_into().unwrap()) as *const T
+        }
+    }
+
+    /// Copies the units over to the current buffer and terminates it.
+    ///
+    /// # Safety
+    /// The current buffer must be able to hold units plus a terminator.
+    unsafe fn write(&mut self, units: &[T]) {
+        let buffer = self.data_ptr() as *mut T;
+        ptr::copy_nonoverlapping(units.as_ptr(), buffer, units.len());
+        ptr::write_bytes(buffer.add(units.len()), 0, 1);
+        self.length = units.len();
+    }
+}
 
-        String::from_utf8_lossy(bytes.as_slice()).to_string()
+impl<T: DLStringUnit> Default for DLBasicString<T> {
+    fn default() -> Self {
+        Self::new()
     }
 }
 
+impl<T: DLStringUnit> fmt::Display for DLBasicString<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&T::decode_lossy(self.as_slice()))
+    }
+}
+
+impl<T: DLStringUnit> fmt::Debug for DLBasicString<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("DLBasicString")
+            .field("value", &T::decode_lossy(self.as_slice()))
+            .field("length", &self.length)
+            .field("capacity", &self.capacity)
+            .finish()
+    }
+}
+
+impl<T: DLStringUnit> PartialEq<str> for DLBasicString<T> {
+    fn eq(&self, other: &str) -> bool {
+        self.as_slice() == T::encode(other).as_slice()
+    }
+}
+
+impl<T: DLStringUnit> PartialEq<&str> for DLBasicString<T> {
+    fn eq(&self, other: &&str) -> bool {
+        self == *other
+    }
+}
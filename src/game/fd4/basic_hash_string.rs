@@ -1,17 +1,100 @@
-use std::ffi;
-
 use zerocopy::{FromBytes, FromZeroes};
 
-use crate::game::dl::DLWString;
+use crate::game::dl::{DLAllocatorRef, DLWString};
 
 #[repr(C)]
 #[derive(FromZeroes, FromBytes)]
 pub struct FD4BasicHashString {
     pub vftable: usize,
-    pub allocator: usize,
+    pub allocator: DLAllocatorRef,
     pub string: DLWString,
     pub unk1: usize,
     pub hash: u32,
     pub needs_hashing: u8,
-    pub pad: [u8; 7],
+    pub pad: [u8; 3],
+}
+
+impl FD4BasicHashString {
+    /// Creates a hash string that is already hashed. Lookups only compare
+    /// hashes so the string itself is left empty, as are the vftable and
+    /// allocator. Never hand these to the game in places where it will take
+    /// ownership of the string.
+    pub fn from_hash(hash: u32) -> Self {
+        let mut result = Self::new_zeroed();
+        result.string.capacity = 7;
+        result.unk1 = 1;
+        result.hash = hash;
+        result
+    }
+
+    /// Creates a hash string for a resource name. See `from_hash`. The name
+    /// is kept around if it fits in the string's inline buffer.
+    pub fn from_name(name: &str) -> Self {
+        let mut result = Self::from_hash(hash_resource_name(name));
+        if let Some(string) = DLWString::from_str_inline(name) {
+            result.string = string;
+        }
+        result
+    }
+
+    /// Returns the hash for the string, computing it if the game hasn't
+    /// gotten around to doing so yet.
+    pub fn computed_hash(&self) -> u32 {
+        if self.needs_hashing == 0 {
+            self.hash
+        } else {
+            hash_resource_name(&self.string.to_string())
+        }
+    }
+
+    /// Checks hash_resource_name against the hash the game computed. None if
+    /// the game hasn't hashed the string yet or no longer has it.
+    pub fn hash_matches_name(&self) -> Option<bool> {
+        if self.needs_hashing != 0 || self.string.is_empty() {
+            return None;
+        }
+
+        Some(hash_resource_name(&self.string.to_string()) == self.hash)
+    }
+
+    /// Computes the hash from the current string like the game does before
+    /// the first lookup.
+    pub fn rehash(&mut self) {
+        self.hash = hash_resource_name(&self.string.to_string());
+        self.needs_hashing = 0;
+    }
+}
+
+const FNV_OFFSET_BASIS: u32 = 0x811c9dc5;
+const FNV_PRIME: u32 = 0x01000193;
+
+/// Computes the hash FD4BasicHashString uses to identify resources, FNV-1a
+/// ran over the UTF-16 code units of the normalized name. None of the
+/// captures so far hold both a name and the hash the game computed for it,
+/// so this has yet to be checked against a hash read from the game. The
+/// hash dictionary checks it against whatever names the game still has.
+pub fn hash_resource_name(name: &str) -> u32 {
+    name.encode_utf16()
+        .map(normalize_code_unit)
+        .fold(FNV_OFFSET_BASIS, |hash, unit| {
+            (hash ^ unit as u32).wrapping_mul(FNV_PRIME)
+        })
+}
+
+/// Normalizes a resource name the same way the hashing does. Resource names
+/// are case insensitive and don't care about the type of path separator.
+pub fn normalize_resource_name(name: &str) -> String {
+    let units = name.encode_utf16()
+        .map(normalize_code_unit)
+        .collect::<Vec<u16>>();
+
+    String::from_utf16_lossy(units.as_slice())
+}
+
+fn normalize_code_unit(unit: u16) -> u16 {
+    match unit {
+        0x41..=0x5a => unit + 0x20,
+        0x5c => 0x2f,
+        _ => unit,
+    }
 }
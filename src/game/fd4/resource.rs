@@ -1,8 +1,11 @@
 use core::ffi;
+use std::ptr;
+use std::slice;
 
 use zerocopy::{FromBytes, FromZeroes};
 
-use crate::game::fd4::FD4BasicHashString;
+use crate::game::dl::{DLAllocatorRef, DLPlainLightMutex, LockedIter};
+use crate::game::fd4::{normalize_resource_name, FD4BasicHashString};
 use crate::util::singleton::DLRFLocatable;
 
 /// Represents a managed resource.
@@ -63,64 +66,142 @@ pub struct FD4ResCapHeader<'a, TRes> {
 #[derive(FromBytes, FromZeroes)]
 pub struct FD4ResCapHolder<'a, TRes> {
     pub vftable: usize,
-    pub allocator: usize,
+    pub allocator: DLAllocatorRef,
     pub owning_repository: usize,
     pub unk18: u32,
     pub capacity: u32,
-    pub map: &'a FD4ResCap<'a, TRes>,
+    /// Array of `capacity` slots, each one pointing to the head of a linked
+    /// list of ResCaps or null if the slot is empty.
+    pub map: *const *const FD4ResCap<'a, TRes>,
 }
 
-// TODO: implement an actual hashmap type for this
-impl<TRes> FD4ResCapHolder<'_, TRes> {
-    pub fn iter(&self) -> ResIterator<TRes> {
+impl<'a, TRes> FD4ResCapHolder<'a, TRes> {
+    pub fn iter(&self) -> ResIterator<'_, TRes> {
         ResIterator {
             current_index: 0,
-            last_item: None,
+            next_item: ptr::null(),
             repository: self,
         }
     }
+
+    /// Iterates while holding the mutex guarding the map so the game's
+    /// loader threads can't modify it in the meantime. The holder itself
+    /// doesn't know about its mutex, it usually lives in the repository
+    /// embedding the holder.
+    pub fn iter_locked<'m>(&'m self, mutex: &'m DLPlainLightMutex) -> LockedIter<'m, ResIterator<'m, TRes>> {
+        LockedIter::new(mutex.lock(), self.iter())
+    }
+
+    /// Returns the slots of the map's first layer.
+    pub fn slots(&self) -> &[*const FD4ResCap<'a, TRes>] {
+        if self.map.is_null() {
+            return &[];
+        }
+
+        unsafe { slice::from_raw_parts(self.map, self.capacity as usize) }
+    }
+
+    /// Looks up a resource by its name. This compares the names the game
+    /// keeps instead of going through `get_by_hash` as hash_resource_name has
+    /// yet to be checked against the game's own hashes.
+    pub fn get(&self, name: &str) -> Option<&FD4ResCap<'_, TRes>> {
+        let name = normalize_resource_name(name);
+        self.iter()
+            .find(|res_cap| normalize_resource_name(&res_cap.header.name.string.to_string()) == name)
+    }
+
+    /// Looks up a resource by the hash of its name. Picks the slot like the
+    /// game does and walks that slot's linked list until the hashes match.
+    pub fn get_by_hash(&self, hash: u32) -> Option<&FD4ResCap<'a, TRes>> {
+        if self.capacity == 0 {
+            return None;
+        }
+
+        let head = self.slots()[(hash % self.capacity) as usize];
+        SlotIterator { current: head }
+            .find(|res_cap| res_cap.header.name.hash == hash)
+    }
+
+    /// Gathers how well the map is being utilized.
+    pub fn stats(&self) -> FD4ResCapHolderStats {
+        let mut stats = FD4ResCapHolderStats {
+            capacity: self.capacity,
+            ..Default::default()
+        };
+
+        for head in self.slots() {
+            let chain_length = SlotIterator { current: *head }.count() as u32;
+            if chain_length == 0 {
+                continue;
+            }
+
+            stats.occupied_slots += 1;
+            stats.entries += chain_length;
+            stats.longest_chain = stats.longest_chain.max(chain_length);
+        }
+
+        stats
+    }
 }
 
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct FD4ResCapHolderStats {
+    pub capacity: u32,
+    pub occupied_slots: u32,
+    pub entries: u32,
+    pub longest_chain: u32,
+}
+
+impl FD4ResCapHolderStats {
+    /// Amount of entries per slot.
+    pub fn load_factor(&self) -> f32 {
+        if self.capacity == 0 {
+            return 0.0;
+        }
+
+        self.entries as f32 / self.capacity as f32
+    }
+}
+
+/// Walks the linked list of a single slot.
+struct SlotIterator<'a, TRes> {
+    current: *const FD4ResCap<'a, TRes>,
+}
+
+impl<'a, TRes> Iterator for SlotIterator<'a, TRes> {
+    type Item = &'a FD4ResCap<'a, TRes>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let res_cap = unsafe { self.current.as_ref()? };
+        self.current = res_cap.header.next_item;
+        Some(res_cap)
+    }
+}
+
+/// Iterates over all ResCaps in a holder. Use iter_locked to get
+/// exclusive access to the map for as long as the iterator exists.
 pub struct ResIterator<'a, TRes> {
-    // Will have to store lock for mutex in here such that we have guaranteed
-    // exclusivity to the hashmap for as long as the iterator exists.
     current_index: u32,
-    last_item: Option<&'a FD4ResCap<'a, TRes>>,
+    next_item: *const FD4ResCap<'a, TRes>,
     repository: &'a FD4ResCapHolder<'a, TRes>,
 }
 
 impl<'a, T> Iterator for ResIterator<'a, T> {
     type Item = &'a FD4ResCap<'a, T>;
 
-    // TODO: wtf is this supposed to be lmao
     fn next(&mut self) -> Option<Self::Item> {
-        // // Continue in the linked list if we're in any
-        // if let Some(last) = self.last_item {
-        //     if let Some(item) = unsafe { last.header.next_item.as_ref() } {
-        //         self.last_item = Some(item);
-        //         return Some(item);
-        //     }
-        // }
-        //
-        // // Search for the next occupied slot and start from the head
-        // for i in self.current_index..self.repository.capacity {
-        //     self.current_index += 1;
-        //
-        //     let slot_ptr = unsafe {
-        //         let slot_offset = 8 * i as usize;
-        //         *((self.repository.map as *const FD4ResCap<'a, _> as usize + slot_offset) as *const usize)
-        //     };
-        //
-        //     if slot_ptr != 0x0 {
-        //         let res_cap = unsafe {
-        //             std::mem::transmute::<usize, Self::Item>(slot_ptr)
-        //         };
-        //         self.last_item = Some(res_cap);
-        //         return Some(res_cap);
-        //     }
-        // }
-
-        None
+        // Search for the next occupied slot if we've exhausted the current
+        // slot's linked list.
+        let slots = self.repository.slots();
+        while self.next_item.is_null() {
+            let slot = slots.get(self.current_index as usize)?;
+            self.current_index += 1;
+            self.next_item = *slot;
+        }
+
+        let res_cap = unsafe { &*self.next_item };
+        self.next_item = res_cap.header.next_item;
+        Some(res_cap)
     }
 }
 
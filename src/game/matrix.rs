@@ -1,9 +1,245 @@
+use std::ops;
+
 use zerocopy::{FromBytes, FromZeroes};
 
+/// Single row of a Matrix4X4.
+#[repr(C)]
+#[derive(FromBytes, FromZeroes, Debug, Default, Clone, Copy, PartialEq)]
+pub struct Matrix4(pub f32, pub f32, pub f32, pub f32);
+
+impl Matrix4 {
+    pub const fn from_array(values: [f32; 4]) -> Self {
+        Self(values[0], values[1], values[2], values[3])
+    }
+
+    pub const fn to_array(self) -> [f32; 4] {
+        [self.0, self.1, self.2, self.3]
+    }
+}
+
+/// Row-major 4x4 matrix. The game uses row vectors, so a point is
+/// transformed by multiplying it from the left and a matrix product applies
+/// the left matrix first.
 #[repr(C)]
-#[derive(FromBytes, FromZeroes)]
-pub struct Matrix4(f32, f32, f32, f32);
+#[derive(FromBytes, FromZeroes, Debug, Default, Clone, Copy, PartialEq)]
+pub struct Matrix4X4(pub Matrix4, pub Matrix4, pub Matrix4, pub Matrix4);
+
+impl Matrix4X4 {
+    pub const IDENTITY: Self = Self::from_rows([
+        [1.0, 0.0, 0.0, 0.0],
+        [0.0, 1.0, 0.0, 0.0],
+        [0.0, 0.0, 1.0, 0.0],
+        [0.0, 0.0, 0.0, 1.0],
+    ]);
+
+    pub const fn from_rows(rows: [[f32; 4]; 4]) -> Self {
+        Self(
+            Matrix4::from_array(rows[0]),
+            Matrix4::from_array(rows[1]),
+            Matrix4::from_array(rows[2]),
+            Matrix4::from_array(rows[3]),
+        )
+    }
+
+    pub const fn rows(&self) -> [[f32; 4]; 4] {
+        [self.0.to_array(), self.1.to_array(), self.2.to_array(), self.3.to_array()]
+    }
+
+    pub fn translation(offset: Vector4) -> Self {
+        Self::from_rows([
+            [1.0, 0.0, 0.0, 0.0],
+            [0.0, 1.0, 0.0, 0.0],
+            [0.0, 0.0, 1.0, 0.0],
+            [offset.x, offset.y, offset.z, 1.0],
+        ])
+    }
+
+    pub fn transpose(&self) -> Self {
+        let rows = self.rows();
+        let mut result = [[0.0; 4]; 4];
+        for (row, values) in result.iter_mut().enumerate() {
+            for (column, value) in values.iter_mut().enumerate() {
+                *value = rows[column][row];
+            }
+        }
+
+        Self::from_rows(result)
+    }
+
+    /// Transforms a row vector, w included.
+    pub fn transform(&self, vector: Vector4) -> Vector4 {
+        let rows = self.rows();
+        let input = [vector.x, vector.y, vector.z, vector.w];
+        let mut output = [0.0; 4];
+        for (column, value) in output.iter_mut().enumerate() {
+            *value = (0..4).map(|row| input[row] * rows[row][column]).sum();
+        }
+
+        Vector4 { x: output[0], y: output[1], z: output[2], w: output[3] }
+    }
+
+    /// Transforms a position, treating w as 1.
+    pub fn transform_point(&self, point: Vector4) -> Vector4 {
+        self.transform(Vector4 { w: 1.0, ..point })
+    }
+
+    pub fn determinant(&self) -> f32 {
+        let m = self.rows();
+        let cofactors = Self::cofactors(&m);
+        (0..4).map(|column| m[0][column] * cofactors[0][column]).sum()
+    }
+
+    /// Inverts the matrix, returns None if it's singular.
+    pub fn inverse(&self) -> Option<Self> {
+        let m = self.rows();
+        let cofactors = Self::cofactors(&m);
+        let determinant: f32 = (0..4).map(|column| m[0][column] * cofactors[0][column]).sum();
+        if determinant.abs() <= f32::EPSILON {
+            return None;
+        }
+
+        // The inverse is the transposed cofactor matrix over the determinant.
+        let mut result = [[0.0; 4]; 4];
+        for (row, values) in result.iter_mut().enumerate() {
+            for (column, value) in values.iter_mut().enumerate() {
+                *value = cofactors[column][row] / determinant;
+            }
+        }
+
+        Some(Self::from_rows(result))
+    }
+
+    fn cofactors(m: &[[f32; 4]; 4]) -> [[f32; 4]; 4] {
+        let mut cofactors = [[0.0; 4]; 4];
+        for (row, values) in cofactors.iter_mut().enumerate() {
+            for (column, value) in values.iter_mut().enumerate() {
+                let sign = if (row + column) % 2 == 0 { 1.0 } else { -1.0 };
+                *value = sign * Self::minor(m, row, column);
+            }
+        }
+
+        cofactors
+    }
+
+    /// Determinant of the 3x3 matrix left after removing a row and column.
+    fn minor(m: &[[f32; 4]; 4], row: usize, column: usize) -> f32 {
+        let rows = (0..4).filter(|r| *r != row).collect::<Vec<_>>();
+        let columns = (0..4).filter(|c| *c != column).collect::<Vec<_>>();
+        let at = |r: usize, c: usize| m[rows[r]][columns[c]];
 
+        at(0, 0) * (at(1, 1) * at(2, 2) - at(1, 2) * at(2, 1))
+            - at(0, 1) * (at(1, 0) * at(2, 2) - at(1, 2) * at(2, 0))
+            + at(0, 2) * (at(1, 0) * at(2, 1) - at(1, 1) * at(2, 0))
+    }
+}
+
+impl ops::Mul for Matrix4X4 {
+    type Output = Self;
+
+    fn mul(self, rhs: Self) -> Self::Output {
+        let (lhs, rhs) = (self.rows(), rhs.rows());
+        let mut result = [[0.0; 4]; 4];
+        for (row, values) in result.iter_mut().enumerate() {
+            for (column, value) in values.iter_mut().enumerate() {
+                *value = (0..4).map(|i| lhs[row][i] * rhs[i][column]).sum();
+            }
+        }
+
+        Self::from_rows(result)
+    }
+}
+
+/// Position or direction as the game stores them, padded out to 16 bytes.
 #[repr(C)]
-#[derive(FromBytes, FromZeroes)]
-pub struct Matrix4X4(Matrix4, Matrix4, Matrix4, Matrix4);
+#[derive(FromBytes, FromZeroes, Debug, Default, Clone, Copy, PartialEq)]
+pub struct Vector4 {
+    pub x: f32,
+    pub y: f32,
+    pub z: f32,
+    pub w: f32,
+}
+
+impl Vector4 {
+    pub const fn new(x: f32, y: f32, z: f32) -> Self {
+        Self { x, y, z, w: 0.0 }
+    }
+
+    /// Distance between two positions, ignoring w.
+    pub fn distance(&self, other: &Vector4) -> f32 {
+        (*self - *other).length()
+    }
+
+    /// Dot product of the x, y and z components.
+    pub fn dot(&self, other: &Vector4) -> f32 {
+        self.x * other.x + self.y * other.y + self.z * other.z
+    }
+
+    pub fn cross(&self, other: &Vector4) -> Vector4 {
+        Vector4::new(
+            self.y * other.z - self.z * other.y,
+            self.z * other.x - self.x * other.z,
+            self.x * other.y - self.y * other.x,
+        )
+    }
+
+    /// Length of the x, y and z components.
+    pub fn length(&self) -> f32 {
+        self.dot(self).sqrt()
+    }
+
+    /// Scales x, y and z to a length of 1, zero vectors are left alone.
+    pub fn normalize(&self) -> Vector4 {
+        let length = self.length();
+        if length == 0.0 {
+            return *self;
+        }
+
+        Vector4 { w: self.w, ..*self * (1.0 / length) }
+    }
+}
+
+/// Operators work on x, y and z, the result has a w of 0.
+impl ops::Add for Vector4 {
+    type Output = Self;
+
+    fn add(self, rhs: Self) -> Self::Output {
+        Vector4::new(self.x + rhs.x, self.y + rhs.y, self.z + rhs.z)
+    }
+}
+
+impl ops::Sub for Vector4 {
+    type Output = Self;
+
+    fn sub(self, rhs: Self) -> Self::Output {
+        Vector4::new(self.x - rhs.x, self.y - rhs.y, self.z - rhs.z)
+    }
+}
+
+impl ops::Mul<f32> for Vector4 {
+    type Output = Self;
+
+    fn mul(self, rhs: f32) -> Self::Output {
+        Vector4::new(self.x * rhs, self.y * rhs, self.z * rhs)
+    }
+}
+
+impl ops::Neg for Vector4 {
+    type Output = Self;
+
+    fn neg(self) -> Self::Output {
+        Vector4::new(-self.x, -self.y, -self.z)
+    }
+}
+
+/// Position on the screen in pixels, the origin is the top left corner.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct Vector2 {
+    pub x: f32,
+    pub y: f32,
+}
+
+impl Vector2 {
+    pub const fn new(x: f32, y: f32) -> Self {
+        Self { x, y }
+    }
+}
@@ -2,4 +2,5 @@ pub mod cs;
 pub mod dl;
 pub mod fd4;
 pub mod matrix;
+pub mod stl;
 pub mod world_area_time;
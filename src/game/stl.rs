@@ -0,0 +1,9 @@
+mod list;
+mod tree;
+mod unordered_map;
+mod vector;
+
+pub use list::*;
+pub use tree::*;
+pub use unordered_map::*;
+pub use vector::*;
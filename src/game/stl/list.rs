@@ -0,0 +1,73 @@
+use std::marker::PhantomData;
+
+use zerocopy::FromZeroes;
+
+use crate::game::dl::DLAllocatorRef;
+
+#[repr(C)]
+#[derive(FromZeroes)]
+pub struct StdListNode<T> {
+    pub next: *mut StdListNode<T>,
+    pub prev: *mut StdListNode<T>,
+    pub value: T,
+}
+
+/// MSVC's std::list. A circular doubly linked list where the head node is a
+/// sentinel that doesn't hold a value. An empty list has a head that points
+/// to itself both ways.
+#[repr(C)]
+#[derive(FromZeroes)]
+pub struct StdList<T> {
+    pub allocator: DLAllocatorRef,
+    pub head: *mut StdListNode<T>,
+    pub size: usize,
+}
+
+impl<T> StdList<T> {
+    pub fn len(&self) -> usize {
+        self.size
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.size == 0 || self.head.is_null()
+    }
+
+    pub fn iter(&self) -> StdListIter<'_, T> {
+        let current = match unsafe { self.head.as_ref() } {
+            Some(head) => head.next as *const _,
+            None => self.head as *const _,
+        };
+
+        StdListIter {
+            remaining: if self.head.is_null() { 0 } else { self.size },
+            current,
+            phantom_data: PhantomData,
+        }
+    }
+}
+
+pub struct StdListIter<'a, T> {
+    remaining: usize,
+    current: *const StdListNode<T>,
+    phantom_data: PhantomData<&'a T>,
+}
+
+impl<'a, T> Iterator for StdListIter<'a, T> {
+    type Item = &'a T;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.remaining == 0 {
+            return None;
+        }
+
+        let node = unsafe { self.current.as_ref()? };
+        self.remaining -= 1;
+        self.current = node.next;
+
+        Some(&node.value)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (0, Some(self.remaining))
+    }
+}
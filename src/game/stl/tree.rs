@@ -0,0 +1,181 @@
+use std::cmp::Ordering;
+use std::marker::PhantomData;
+
+use zerocopy::{FromBytes, FromZeroes};
+
+use crate::game::dl::DLAllocatorRef;
+
+/// MSVC's std::pair. Rust tuples don't have a defined layout so map values
+/// are represented with this instead.
+#[repr(C)]
+#[derive(FromZeroes, FromBytes)]
+pub struct StdPair<K, V> {
+    pub first: K,
+    pub second: V,
+}
+
+#[repr(C)]
+#[derive(FromZeroes)]
+pub struct StdTreeNode<T> {
+    pub left: *mut StdTreeNode<T>,
+    pub parent: *mut StdTreeNode<T>,
+    pub right: *mut StdTreeNode<T>,
+    pub color: u8,
+    pub is_nil: u8,
+    pub value: T,
+}
+
+/// MSVC's red-black tree as used by both std::map and std::set.
+/// The tree owns a head node that serves as a sentinel and never holds a
+/// value. The head's parent points at the root, its left at the smallest
+/// element and its right at the largest element. Every leaf points back at
+/// the head, which is the only node that has is_nil set.
+///
+/// ```text
+///            +------+
+///     +----->| head |<------+
+///     |      +------+       |
+///     |   left  |  parent   | right
+///     |         v           |
+///     |      +------+       |
+///     |      | root |       |
+///     |      +------+       |
+///     |      /      \       |
+///     |  +----+    +----+   |
+///     +--| lo |    | hi |---+
+///        +----+    +----+
+/// ```
+#[repr(C)]
+#[derive(FromZeroes)]
+pub struct StdTree<T> {
+    pub allocator: DLAllocatorRef,
+    pub head: *mut StdTreeNode<T>,
+    pub size: usize,
+}
+
+pub type StdMap<K, V> = StdTree<StdPair<K, V>>;
+pub type StdSet<T> = StdTree<T>;
+/// A tree whose element type hasn't been mapped. Only its size can be
+/// trusted, the elements read back as empty.
+pub type OpaqueStdTree = StdTree<[u8; 0]>;
+
+impl<T> StdTree<T> {
+    pub fn len(&self) -> usize {
+        self.size
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.size == 0 || self.head.is_null()
+    }
+
+    /// Iterates over the elements in order.
+    pub fn iter(&self) -> StdTreeIter<'_, T> {
+        let current = match unsafe { self.head.as_ref() } {
+            Some(head) => head.left as *const _,
+            None => self.head as *const _,
+        };
+
+        StdTreeIter {
+            remaining: if self.head.is_null() { 0 } else { self.size },
+            current,
+            phantom_data: PhantomData,
+        }
+    }
+
+    /// Walks down the tree from the root using the supplied comparison.
+    /// The closure should return how the searched for element relates to
+    /// the node's value.
+    pub fn find_by(&self, mut f: impl FnMut(&T) -> Ordering) -> Option<&T> {
+        let head = unsafe { self.head.as_ref()? };
+
+        let mut current = head.parent as *const StdTreeNode<T>;
+        for _ in 0..=self.size {
+            let node = unsafe { current.as_ref()? };
+            if node.is_nil != 0 {
+                return None;
+            }
+
+            current = match f(&node.value) {
+                Ordering::Less => node.left,
+                Ordering::Greater => node.right,
+                Ordering::Equal => return Some(&node.value),
+            };
+        }
+
+        None
+    }
+}
+
+impl<T: Ord> StdSet<T> {
+    pub fn contains(&self, value: &T) -> bool {
+        self.find_by(|v| value.cmp(v)).is_some()
+    }
+}
+
+impl<K: Ord, V> StdMap<K, V> {
+    pub fn get(&self, key: &K) -> Option<&V> {
+        self.find_by(|pair| key.cmp(&pair.first))
+            .map(|pair| &pair.second)
+    }
+
+    pub fn keys(&self) -> impl Iterator<Item = &K> {
+        self.iter().map(|pair| &pair.first)
+    }
+
+    pub fn values(&self) -> impl Iterator<Item = &V> {
+        self.iter().map(|pair| &pair.second)
+    }
+}
+
+pub struct StdTreeIter<'a, T> {
+    // Bounded by the tree's size so that a corrupted or concurrently
+    // modified tree can't send us around in circles.
+    remaining: usize,
+    current: *const StdTreeNode<T>,
+    phantom_data: PhantomData<&'a T>,
+}
+
+impl<'a, T> Iterator for StdTreeIter<'a, T> {
+    type Item = &'a T;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.remaining == 0 {
+            return None;
+        }
+
+        let node = unsafe { self.current.as_ref()? };
+        if node.is_nil != 0 {
+            return None;
+        }
+
+        self.remaining -= 1;
+        self.current = unsafe { successor(node) };
+
+        Some(&node.value)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (0, Some(self.remaining))
+    }
+}
+
+/// Finds the next node in order. Either the leftmost node of the right
+/// subtree or, when there is none, the first ancestor we reach from its left.
+unsafe fn successor<T>(node: &StdTreeNode<T>) -> *const StdTreeNode<T> {
+    if (*node.right).is_nil == 0 {
+        let mut current = node.right as *const StdTreeNode<T>;
+        while (*(*current).left).is_nil == 0 {
+            current = (*current).left;
+        }
+        return current;
+    }
+
+    let mut current = node as *const StdTreeNode<T>;
+    let mut parent = node.parent as *const StdTreeNode<T>;
+    while (*parent).is_nil == 0 && current == (*parent).right {
+        current = parent;
+        parent = (*parent).parent;
+    }
+
+    parent
+}
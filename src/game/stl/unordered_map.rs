@@ -0,0 +1,46 @@
+use zerocopy::FromZeroes;
+
+use crate::game::stl::{StdList, StdListIter, StdListNode, StdPair, StdVector};
+
+/// MSVC's std::unordered_map. All elements live in a single std::list, the
+/// bucket vector holds a pair of iterators (first and last node) into that
+/// list for every bucket.
+#[repr(C)]
+#[derive(FromZeroes)]
+pub struct StdUnorderedMap<K, V> {
+    pub max_load_factor: f32,
+    _pad4: u32,
+    pub list: StdList<StdPair<K, V>>,
+    pub buckets: StdVector<*mut StdListNode<StdPair<K, V>>>,
+    pub mask: usize,
+    pub max_index: usize,
+}
+
+impl<K, V> StdUnorderedMap<K, V> {
+    pub fn len(&self) -> usize {
+        self.list.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.list.is_empty()
+    }
+
+    pub fn bucket_count(&self) -> usize {
+        self.buckets.len() / 2
+    }
+
+    /// Iterates over the elements in the order of the underlying list.
+    pub fn iter(&self) -> StdListIter<'_, StdPair<K, V>> {
+        self.list.iter()
+    }
+}
+
+impl<K: PartialEq, V> StdUnorderedMap<K, V> {
+    /// Looks up a value by scanning the element list. We don't know the
+    /// hasher used for every instantiation so this doesn't use the buckets.
+    pub fn get(&self, key: &K) -> Option<&V> {
+        self.iter()
+            .find(|pair| &pair.first == key)
+            .map(|pair| &pair.second)
+    }
+}
@@ -0,0 +1,67 @@
+use std::slice;
+
+use zerocopy::FromZeroes;
+
+use crate::game::dl::DLAllocatorRef;
+
+/// MSVC's std::vector. Holds a pointer to the first element, one past the
+/// last element and one past the end of the allocation.
+#[repr(C)]
+#[derive(FromZeroes)]
+pub struct StdVector<T> {
+    pub allocator: DLAllocatorRef,
+    pub first: *mut T,
+    pub last: *mut T,
+    pub end: *mut T,
+}
+
+impl<T> StdVector<T> {
+    /// Whether the pointers look like a vector's. Either they're all null or
+    /// they're aligned, in order and a whole number of elements apart.
+    pub fn is_well_formed(&self) -> bool {
+        let (first, last, end) = (self.first as usize, self.last as usize, self.end as usize);
+        if first == 0 {
+            return last == 0 && end == 0;
+        }
+
+        let size = std::mem::size_of::<T>().max(1);
+        first % std::mem::align_of::<T>() == 0
+            && first <= last
+            && last <= end
+            && (last - first) % size == 0
+            && (end - first) % size == 0
+    }
+
+    /// Number of elements, 0 if the vector isn't well formed.
+    pub fn len(&self) -> usize {
+        if self.first.is_null() || !self.is_well_formed() {
+            return 0;
+        }
+
+        (self.last as usize - self.first as usize) / std::mem::size_of::<T>()
+    }
+
+    pub fn capacity(&self) -> usize {
+        if self.first.is_null() || !self.is_well_formed() {
+            return 0;
+        }
+
+        (self.end as usize - self.first as usize) / std::mem::size_of::<T>()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn as_slice(&self) -> &[T] {
+        if self.first.is_null() {
+            return &[];
+        }
+
+        unsafe { slice::from_raw_parts(self.first, self.len()) }
+    }
+
+    pub fn iter(&self) -> slice::Iter<'_, T> {
+        self.as_slice().iter()
+    }
+}
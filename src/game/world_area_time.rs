@@ -1,3 +1,5 @@
+use std::fmt;
+
 use zerocopy::{FromBytes, FromZeroes};
 
 use crate::util::singleton::DLRFLocatable;
@@ -7,46 +9,173 @@ use crate::util::singleton::DLRFLocatable;
 pub struct WorldAreaTime {
     pub unk0: u64,
     pub clock: WorldAreaTimeClock,
-    // TODO: rest
+    // TODO: rest. The weather state, pending weather transitions and the
+    // time progression rate live somewhere past here but haven't been
+    // located yet. Pausing or scaling time and forcing weather need those
+    // fields mapped from a capture first.
+}
+
+impl WorldAreaTime {
+    pub fn time_of_day(&self) -> Result<TimeOfDay, TimeOfDayError> {
+        self.clock.time_of_day()
+    }
+
+    /// Jumps to the given time of day, resetting the milliseconds.
+    pub fn set_time_of_day(&mut self, hours: u8, minutes: u8, seconds: u8) -> Result<(), TimeOfDayError> {
+        let time_of_day = TimeOfDay::from_hms(hours, minutes, seconds)?;
+        self.clock.set_time_of_day(time_of_day);
+        Ok(())
+    }
 }
 
 impl DLRFLocatable for WorldAreaTime {
     const DLRF_NAME: &'static str = "WorldAreaTime";
 }
 
-#[derive(FromBytes, FromZeroes)]
+/// The in-game date and time packed into bitfields. Setters truncate values
+/// to the width of their field.
+#[repr(transparent)]
+#[derive(FromBytes, FromZeroes, Debug, Clone, Copy, PartialEq, Eq)]
 pub struct WorldAreaTimeClock(u64);
 
+macro_rules! clock_field {
+    ($get:ident, $set:ident, $shift:literal, $width:literal) => {
+        pub fn $get(&self) -> u64 {
+            (self.0 >> $shift) & ((1 << $width) - 1)
+        }
+
+        pub fn $set(&mut self, value: u64) {
+            let mask = ((1 << $width) - 1) << $shift;
+            self.0 = (self.0 & !mask) | ((value << $shift) & mask);
+        }
+    };
+}
+
 impl WorldAreaTimeClock {
-    pub fn year(&self) -> u64 {
-        (self.0 >> 00) & 0b111111111111
+    pub const fn from_raw(value: u64) -> Self {
+        Self(value)
     }
 
-    pub fn milliseconds(&self) -> u64 {
-        (self.0 >> 12) & 0b1111111111
+    pub const fn as_raw(&self) -> u64 {
+        self.0
     }
 
-    pub fn month(&self) -> u64 {
-        (self.0 >> 22) & 0b1111
+    clock_field!(year, set_year, 0, 12);
+    clock_field!(milliseconds, set_milliseconds, 12, 10);
+    clock_field!(month, set_month, 22, 4);
+    clock_field!(day_of_week, set_day_of_week, 26, 3);
+    clock_field!(day, set_day, 29, 5);
+    clock_field!(hours, set_hours, 34, 5);
+    clock_field!(minutes, set_minutes, 39, 6);
+    clock_field!(seconds, set_seconds, 45, 6);
+
+    /// The fields are wide enough to hold values past the end of a day,
+    /// those are handed back as an error.
+    pub fn time_of_day(&self) -> Result<TimeOfDay, TimeOfDayError> {
+        TimeOfDay::from_hms_milli(
+            self.hours() as u8,
+            self.minutes() as u8,
+            self.seconds() as u8,
+            self.milliseconds() as u16,
+        )
     }
 
-    pub fn day_of_week(&self) -> u64 {
-        (self.0 >> 26) & 0b111
+    /// Changes the time while leaving the date alone.
+    pub fn set_time_of_day(&mut self, time_of_day: TimeOfDay) {
+        self.set_hours(time_of_day.hours as u64);
+        self.set_minutes(time_of_day.minutes as u64);
+        self.set_seconds(time_of_day.seconds as u64);
+        self.set_milliseconds(time_of_day.milliseconds as u64);
     }
+}
 
-    pub fn day(&self) -> u64 {
-        (self.0 >> 29) & 0b11111
+/// Holds the component that was out of range.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TimeOfDayError {
+    Hours(u8),
+    Minutes(u8),
+    Seconds(u8),
+    Milliseconds(u16),
+}
+
+/// Time on a 24 hour clock without a date.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct TimeOfDay {
+    hours: u8,
+    minutes: u8,
+    seconds: u8,
+    milliseconds: u16,
+}
+
+impl TimeOfDay {
+    pub fn from_hms(hours: u8, minutes: u8, seconds: u8) -> Result<Self, TimeOfDayError> {
+        Self::from_hms_milli(hours, minutes, seconds, 0)
+    }
+
+    pub fn from_hms_milli(
+        hours: u8,
+        minutes: u8,
+        seconds: u8,
+        milliseconds: u16,
+    ) -> Result<Self, TimeOfDayError> {
+        if hours >= 24 {
+            return Err(TimeOfDayError::Hours(hours));
+        }
+        if minutes >= 60 {
+            return Err(TimeOfDayError::Minutes(minutes));
+        }
+        if seconds >= 60 {
+            return Err(TimeOfDayError::Seconds(seconds));
+        }
+        if milliseconds >= 1000 {
+            return Err(TimeOfDayError::Milliseconds(milliseconds));
+        }
+
+        Ok(Self { hours, minutes, seconds, milliseconds })
+    }
+
+    /// Wraps around past midnight.
+    pub fn from_seconds_from_midnight(seconds: u32) -> Self {
+        let seconds = seconds % (24 * 60 * 60);
+
+        Self {
+            hours: (seconds / 3600) as u8,
+            minutes: (seconds / 60 % 60) as u8,
+            seconds: (seconds % 60) as u8,
+            milliseconds: 0,
+        }
     }
 
-    pub fn hours(&self) -> u64 {
-        (self.0 >> 34) & 0b11111
+    pub fn hours(&self) -> u8 {
+        self.hours
     }
 
-    pub fn minutes(&self) -> u64 {
-        (self.0 >> 39) & 0b111111
+    pub fn minutes(&self) -> u8 {
+        self.minutes
     }
 
-    pub fn seconds(&self) -> u64 {
-        (self.0 >> 45) & 0b111111
+    pub fn seconds(&self) -> u8 {
+        self.seconds
+    }
+
+    pub fn milliseconds(&self) -> u16 {
+        self.milliseconds
+    }
+
+    pub fn seconds_from_midnight(&self) -> u32 {
+        self.hours as u32 * 3600 + self.minutes as u32 * 60 + self.seconds as u32
+    }
+}
+
+impl fmt::Display for TimeOfDay {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{:02}:{:02}:{:02}.{:03}",
+            self.hours,
+            self.minutes,
+            self.seconds,
+            self.milliseconds,
+        )
     }
 }
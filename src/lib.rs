@@ -1,9 +1,10 @@
 use broadsword::dll;
 
 use game::cs::CSCamera;
+use game::cs::{locate_chr_dbg_flags, ChrDbgFlagsError, ChrDbgFlagsRef};
 use game::cs::CSSessionManager;
 use game::cs::WorldState;
-use game::cs::{ChrIns, WorldChrMan, WorldChrManDbg};
+use game::cs::{WorldChrMan, WorldChrManDbg};
 use game::fd4::FlverRepository;
 use game::world_area_time::WorldAreaTime;
 use hudhook::eject;
@@ -13,9 +14,19 @@ use hudhook::imgui::*;
 use hudhook::windows::Win32::Foundation::HINSTANCE;
 use hudhook::Hudhook;
 use hudhook::ImguiRenderLoop;
+use hudhook::MessageFilter;
 use util::debug_display::render_debug_singleton;
+use util::debug_display::render_edit_singleton;
 use util::debug_display::DebugDisplay;
 use util::singleton::DLRFLocatable;
+use free_camera::{FreeCamera, FreeCameraInput, FreeCameraKeys};
+use spectate::{Spectate, SpectateAction, SpectateKeys};
+use util::debug_display::DebugEdit;
+use util::input::{CursorDelta, KeyboardState};
+use session::{SessionEventRecorder, SessionLogComparison, SessionRoster, SessionState, SessionStateTracker};
+use config::{Config, ConfigWatcher};
+use export::ExportConfig;
+use logging::{Logging, LoggingError};
 
 #[cfg(test)]
 pub mod test;
@@ -23,14 +34,18 @@ pub mod test;
 mod game;
 mod util;
 mod export;
+mod session;
+mod free_camera;
+mod spectate;
+mod config;
+mod logging;
 
 use std::ffi;
 use std::mem;
-use std::sync::atomic::AtomicU32;
-use std::sync::atomic::Ordering;
+use std::path::PathBuf;
 use std::sync::OnceLock;
 use std::sync::RwLock;
-use std::time::Instant;
+use std::time::{Instant, SystemTime};
 use windows::core::PCWSTR;
 use crate::game::cs::CSFile;
 use crate::game::dl::DLWString;
@@ -38,8 +53,12 @@ use crate::game::dl::DLWString;
 #[dll::entrypoint]
 pub fn entry(hmodule: usize) -> bool {
     std::thread::spawn(move || {
+        let config_path = config::module_directory(hmodule)
+            .unwrap_or_default()
+            .join(config::CONFIG_FILE_NAME);
+
         if let Err(e) = Hudhook::builder()
-            .with::<ImguiDx12Hooks>(FsTestsHud::new())
+            .with::<ImguiDx12Hooks>(FsTestsHud::new(config_path))
             .with_hmodule(HINSTANCE(hmodule as isize))
             .build()
             .apply()
@@ -69,96 +88,339 @@ pub fn entry(hmodule: usize) -> bool {
     true
 }
 
-struct FsTestsHud;
+struct FsTestsHud {
+    config_watcher: ConfigWatcher,
+    logging: Result<Logging, LoggingError>,
+    session_states: SessionStateTracker,
+    session_roster: SessionRoster,
+    session_events: SessionEventRecorder,
+    session_comparison: SessionLogComparison,
+    free_camera: FreeCamera,
+    free_camera_requested: bool,
+    free_camera_cursor: CursorDelta,
+    free_camera_keys: FreeCameraKeys,
+    chr_dbg_flags: Result<ChrDbgFlagsRef, ChrDbgFlagsError>,
+    spectate: Spectate,
+    /// Spectate actions requested from the overlay, handled next frame.
+    spectate_actions: Vec<SpectateAction>,
+    keyboard: KeyboardState,
+    /// Keeps input from reaching the game while the overlay has focus.
+    capture_input: bool,
+    last_frame: Instant,
+}
 
 impl FsTestsHud {
-    fn new() -> Self {
-        Self {}
+    fn new(config_path: PathBuf) -> Self {
+        if let Some(directory) = config_path.parent() {
+            config::set_directory(directory);
+        }
+
+        let mut config_watcher = ConfigWatcher::new(config_path);
+        let config = config_watcher.reload_if_changed(Instant::now())
+            .unwrap_or_default();
+        let logging = Logging::init(&config.log_file, config.log_level);
+
+        let mut hud = Self {
+            config_watcher,
+            logging,
+            session_states: SessionStateTracker::default(),
+            session_roster: SessionRoster::new(),
+            session_events: Self::session_event_recorder(&config.export),
+            session_comparison: SessionLogComparison::default(),
+            free_camera: FreeCamera::new(),
+            free_camera_requested: false,
+            free_camera_cursor: CursorDelta::default(),
+            free_camera_keys: FreeCameraKeys::default(),
+            chr_dbg_flags: Err(ChrDbgFlagsError::NotConfigured),
+            spectate: Spectate::new(SpectateKeys::default()),
+            spectate_actions: vec![],
+            keyboard: KeyboardState::default(),
+            capture_input: true,
+            last_frame: Instant::now(),
+        };
+
+        hud.apply_config(config);
+        hud
+    }
+
+    fn session_event_recorder(config: &ExportConfig) -> SessionEventRecorder {
+        SessionEventRecorder::new(
+            &config.directory,
+            &config.session_events,
+            config.session_events_max_file_size,
+            config.session_events_max_files,
+        )
+    }
+
+    /// Puts a freshly loaded config into effect.
+    fn apply_config(&mut self, config: Config) {
+        if let Ok(logging) = &self.logging {
+            if let Err(e) = logging.set_level(config.log_level) {
+                tracing::error!("Could not change the log level: {e:?}");
+            }
+        }
+
+        // Only start a new event log if it moved, the recorder keeps the
+        // recent events around.
+        let previous = config::current();
+        let session_events_changed = previous.export.directory != config.export.directory
+            || previous.export.session_events != config.export.session_events
+            || previous.export.session_events_max_file_size != config.export.session_events_max_file_size
+            || previous.export.session_events_max_files != config.export.session_events_max_files;
+        if session_events_changed {
+            self.session_events = Self::session_event_recorder(&config.export);
+        }
+
+        if previous.camera_update != config.camera_update {
+            self.free_camera.configure_update_patch(config.camera_update.as_ref());
+        }
+        if previous.chr_dbg_flags != config.chr_dbg_flags {
+            self.chr_dbg_flags = config.chr_dbg_flags
+                .ok_or(ChrDbgFlagsError::NotConfigured)
+                .and_then(locate_chr_dbg_flags);
+        }
+
+        self.spectate.keys = config.hotkeys.spectate;
+        self.free_camera_keys = config.hotkeys.free_camera;
+        self.capture_input = config.features.capture_input;
+
+        config::set_current(config);
+    }
+
+    fn update_spectate(&mut self) {
+        let mut actions = self.spectate.actions(&self.keyboard);
+        actions.append(&mut self.spectate_actions);
+
+        // Turning the feature off stops spectating.
+        if !config::current().features.spectate {
+            actions.clear();
+            if self.spectate.is_active() {
+                actions.push(SpectateAction::Toggle);
+            }
+        }
+
+        let world_chr_man = util::singleton::get_instance::<WorldChrMan>()
+            .ok()
+            .flatten();
+        let world_chr_man_dbg = util::singleton::get_instance::<WorldChrManDbg>()
+            .ok()
+            .flatten();
+
+        let (Some(world_chr_man), Some(world_chr_man_dbg)) = (world_chr_man, world_chr_man_dbg) else {
+            return;
+        };
+
+        for action in actions {
+            self.spectate.handle(action, world_chr_man, world_chr_man_dbg);
+        }
+
+        self.spectate.update(world_chr_man, world_chr_man_dbg);
+    }
+
+    fn update_free_camera(&mut self, dt: f32) {
+        let camera = util::singleton::get_instance::<CSCamera>()
+            .ok()
+            .flatten();
+
+        let Some(camera) = camera else {
+            return;
+        };
+
+        if self.free_camera_keys.toggle.is_pressed(&self.keyboard) {
+            self.free_camera_requested = !self.free_camera_requested;
+        }
+        if !config::current().features.free_camera {
+            self.free_camera_requested = false;
+        }
+
+        // Pers cam 1 is the one that ends up being rendered.
+        let cam = &mut *camera.pers_cam_1;
+        match (self.free_camera_requested, self.free_camera.is_active()) {
+            (true, false) => self.free_camera.enable(cam),
+            (false, true) => self.free_camera.disable(cam),
+            _ => {},
+        }
+
+        let input = FreeCameraInput::poll(
+            &self.keyboard,
+            &self.free_camera_keys,
+            &mut self.free_camera_cursor,
+            0.005,
+            dt,
+        );
+        self.free_camera.update(cam, &input, dt);
+    }
+
+    fn update_session_states(&mut self) {
+        let session_manager = util::singleton::get_instance::<CSSessionManager>()
+            .ok()
+            .flatten();
+
+        if let Some(session_manager) = session_manager {
+            let now = SystemTime::now();
+            let transition = self.session_states.observe(SessionState::of(session_manager), Instant::now());
+            let roster_changes = self.session_roster.observe(session_manager, now);
+
+            if !config::current().features.record_session_events {
+                return;
+            }
+
+            let result = self.session_events.record_changes(
+                transition.as_ref(),
+                &roster_changes,
+                &self.session_roster,
+                now,
+            );
+
+            if let Err(e) = result {
+                tracing::error!("Could not record session events: {e:?}");
+            }
+        }
+    }
+
+    /// Shows what went wrong loading the config or setting up logging at the
+    /// top of the overlay.
+    fn render_errors(&self, ui: &Ui) {
+        const ERROR_COLOR: [f32; 4] = [1.0, 0.4, 0.4, 1.0];
+
+        if let Some(e) = self.config_watcher.error() {
+            ui.text_colored(ERROR_COLOR, format!("Could not load {}:", self.config_watcher.path().display()));
+            ui.text_wrapped(e.to_string());
+            ui.separator();
+        }
+
+        if let Err(e) = &self.logging {
+            ui.text_colored(ERROR_COLOR, format!("Could not set up logging: {e:?}"));
+            ui.separator();
+        }
     }
 }
 
 const LOG: OnceLock<RwLock<Vec<String>>> = OnceLock::new();
-const LAST_WORLD_STATE: AtomicU32 = AtomicU32::new(0);
 
 impl ImguiRenderLoop for FsTestsHud {
     fn render(&mut self, ui: &mut Ui) {
+        let now = Instant::now();
+        let dt = now.duration_since(self.last_frame).as_secs_f32();
+        self.last_frame = now;
+
+        if let Some(config) = self.config_watcher.reload_if_changed(now) {
+            self.apply_config(config);
+        }
+        let config = config::current();
+
+        self.keyboard.poll();
+        self.keyboard.set_captured(self.capture_input && ui.io().want_capture_keyboard);
+
+        self.update_session_states();
+        self.update_free_camera(dt);
+        self.update_spectate();
+
+        let panels = &config.panels;
         ui.window("Elden Ring Debug")
             .position([0., 0.], imgui::Condition::FirstUseEver)
-            .size([800., 600.], imgui::Condition::FirstUseEver)
+            .size([config.window.width, config.window.height], imgui::Condition::FirstUseEver)
             .build(|| {
-                render_debug_singleton::<WorldChrMan>(&ui);
-                render_debug_singleton::<CSSessionManager>(&ui);
-                render_debug_singleton::<WorldAreaTime>(&ui);
-                render_debug_singleton::<CSCamera>(&ui);
-                render_debug_singleton::<FlverRepository>(&ui);
+                self.render_errors(ui);
+
+                if panels.world_chr_man {
+                    render_debug_singleton::<WorldChrMan>(&ui);
+                }
+                if panels.world_chr_man_dbg {
+                    render_debug_singleton::<WorldChrManDbg>(&ui);
+                }
+                if panels.chr_dbg_flags && ui.collapsing_header("ChrDbg flags", TreeNodeFlags::empty()) {
+                    match &mut self.chr_dbg_flags {
+                        Ok(flags) => {
+                            flags.render_debug(&ui);
+                            flags.render_edit(&ui);
+                        },
+                        Err(e) => ui.text_colored([1.0, 0.4, 0.4, 1.0], format!("No ChrDbg flags: {e:?}")),
+                    }
+                }
+                if panels.session_manager {
+                    render_debug_singleton::<CSSessionManager>(&ui);
+                }
+                if panels.session_history && ui.collapsing_header("Session state history", TreeNodeFlags::empty()) {
+                    if ui.button("Clear history") {
+                        self.session_states.clear();
+                    }
+                    self.session_states.render_debug(&ui);
+                }
+                if panels.session_players && ui.collapsing_header("Session players", TreeNodeFlags::empty()) {
+                    if ui.button("Clear roster") {
+                        self.session_roster.clear();
+                    }
+                    self.session_roster.render_debug(&ui);
+                }
+                if panels.session_events && ui.collapsing_header("Session events", TreeNodeFlags::empty()) {
+                    self.session_events.render_debug(&ui);
+                }
+                if panels.session_diff && ui.collapsing_header("Session log diff", TreeNodeFlags::empty()) {
+                    self.session_comparison.render_edit(&ui);
+                }
+                if panels.world_area_time {
+                    render_edit_singleton::<WorldAreaTime>(&ui);
+                }
+                if panels.camera {
+                    render_debug_singleton::<CSCamera>(&ui);
+                }
+                if panels.free_camera && config.features.free_camera && ui.collapsing_header("Free camera", TreeNodeFlags::empty()) {
+                    ui.checkbox("Enabled", &mut self.free_camera_requested);
+                    self.free_camera.render_debug(&ui);
+                    self.free_camera.render_edit(&ui);
+                }
+                if panels.spectate && config.features.spectate && ui.collapsing_header("Spectate", TreeNodeFlags::empty()) {
+                    let buttons = [
+                        ("Toggle spectate", SpectateAction::Toggle),
+                        ("Previous", SpectateAction::Previous),
+                        ("Next", SpectateAction::Next),
+                        ("Toggle ragdoll", SpectateAction::ToggleRagdoll),
+                    ];
+
+                    for (label, action) in buttons {
+                        if ui.button(label) {
+                            self.spectate_actions.push(action);
+                        }
+                        ui.same_line();
+                    }
+                    ui.new_line();
+
+                    self.spectate.render_debug(&ui);
+                    self.spectate.render_edit(&ui);
+                }
+                if panels.flver_repository {
+                    render_debug_singleton::<FlverRepository>(&ui);
+                }
+                if panels.input && ui.collapsing_header("Input", TreeNodeFlags::empty()) {
+                    ui.checkbox("Keep input from the game while the overlay has focus", &mut self.capture_input);
+                    ui.text(format!("Overlay has keyboard focus: {}", self.keyboard.is_captured()));
+                    ui.text(format!("Free camera toggle: {}", self.free_camera_keys.toggle));
+                }
+                if ui.collapsing_header("Config", TreeNodeFlags::empty()) {
+                    ui.text(format!("Loaded from: {}", self.config_watcher.path().display()));
+                    if ui.button("Reload config") {
+                        if let Some(config) = self.config_watcher.reload() {
+                            self.apply_config(config);
+                        }
+                    }
+                }
             });
     }
-}
 
-// unsafe fn update_spectate() {
-//     static mut SPECTATE_ENABLED: bool = false;
-//     static mut SPECTATE_SLOT: isize = 0;
-//
-//     let world_chr_man_dbg =
-//         util::singleton::get_instance::<WorldChrManDbg>()
-//             .expect("Could not find static for WorldChrManDbg");
-//
-//     let world_chr_man_dbg = match world_chr_man_dbg {
-//         Some(w) => &mut *w,
-//         None => return,
-//     };
-//
-//     if input::is_key_pressed(0x24) {
-//         SPECTATE_ENABLED = !SPECTATE_ENABLED;
-//
-//         if !SPECTATE_ENABLED {
-//             world_chr_man_dbg.cam_override_chr_ins = 0 as *mut ChrIns;  
-//         } else {
-//             SPECTATE_SLOT = 0;
-//         }
-//     }
-//
-//     if SPECTATE_ENABLED {
-//         let world_chr_man = 
-//             util::singleton::get_instance::<WorldChrMan>()
-//                 .expect("Could not find static for WorldChrMan");
-//
-//         let world_chr_man = match world_chr_man {
-//             Some(w) => &mut *w,
-//             None => return,
-//         };
-//
-//         let chrs = world_chr_man.open_field_chr_set.base.iter()
-//             .collect::<Vec<*mut ChrIns>>();
-//
-//         let change: isize = if input::is_key_pressed(0x21) {
-//             -1
-//         } else if input::is_key_pressed(0x22) {
-//             1
-//         } else {
-//             0
-//         };
-//
-//         SPECTATE_SLOT += change;
-//         if SPECTATE_SLOT < 0 {
-//             SPECTATE_SLOT = (chrs.len() - 1) as isize;
-//         } else if SPECTATE_SLOT > chrs.len() as isize - 1 {
-//             SPECTATE_SLOT = 0;
-//         }
-//
-//         let selected_chr = chrs[SPECTATE_SLOT as usize];
-//         if change != 0 {
-//             world_chr_man_dbg.cam_override_chr_ins = selected_chr;
-//         }
-//
-//         if input::is_key_pressed(0x2d) {
-//             let state = (*(*selected_chr).chr_ctrl).chr_ragdoll_state;
-//
-//             (*(*selected_chr).chr_ctrl).chr_ragdoll_state = if state == 0 {
-//                 2
-//             } else {
-//                 0
-//             };
-//         }
-//     }
-// }
+    fn message_filter(&self, io: &Io) -> MessageFilter {
+        let mut filter = MessageFilter::empty();
+        if !self.capture_input {
+            return filter;
+        }
+
+        if io.want_capture_keyboard {
+            filter |= MessageFilter::InputKeyboard;
+        }
+        if io.want_capture_mouse {
+            filter |= MessageFilter::InputMouse;
+        }
+
+        filter
+    }
+}
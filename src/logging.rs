@@ -0,0 +1,73 @@
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::sync::Mutex;
+
+use serde::{Deserialize, Serialize};
+use tracing_subscriber::filter::LevelFilter;
+use tracing_subscriber::layer::SubscriberExt;
+use tracing_subscriber::util::SubscriberInitExt;
+use tracing_subscriber::{fmt, reload, Registry};
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LogLevel {
+    Off,
+    Error,
+    Warn,
+    #[default]
+    Info,
+    Debug,
+    Trace,
+}
+
+impl From<LogLevel> for LevelFilter {
+    fn from(value: LogLevel) -> Self {
+        match value {
+            LogLevel::Off => LevelFilter::OFF,
+            LogLevel::Error => LevelFilter::ERROR,
+            LogLevel::Warn => LevelFilter::WARN,
+            LogLevel::Info => LevelFilter::INFO,
+            LogLevel::Debug => LevelFilter::DEBUG,
+            LogLevel::Trace => LevelFilter::TRACE,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum LoggingError {
+    FileCreation(io::Error),
+    /// Another subscriber was installed already.
+    AlreadyInitialized,
+    Reload(reload::Error),
+}
+
+/// Writes tracing output to a file. The level can be changed afterwards so
+/// it follows the config.
+pub struct Logging {
+    level: reload::Handle<LevelFilter, Registry>,
+}
+
+impl Logging {
+    pub fn init(path: &Path, level: LogLevel) -> Result<Self, LoggingError> {
+        let file = fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .map_err(LoggingError::FileCreation)?;
+
+        let (filter, handle) = reload::Layer::new(LevelFilter::from(level));
+        tracing_subscriber::registry()
+            .with(filter)
+            .with(fmt::layer().with_ansi(false).with_writer(Mutex::new(file)))
+            .try_init()
+            .map_err(|_| LoggingError::AlreadyInitialized)?;
+
+        Ok(Self { level: handle })
+    }
+
+    pub fn set_level(&self, level: LogLevel) -> Result<(), LoggingError> {
+        self.level.reload(LevelFilter::from(level))
+            .map_err(LoggingError::Reload)
+    }
+}
@@ -0,0 +1,9 @@
+mod event;
+mod replay;
+mod roster;
+mod state;
+
+pub use event::*;
+pub use replay::*;
+pub use roster::*;
+pub use state::*;
@@ -0,0 +1,229 @@
+use std::collections::VecDeque;
+use std::fs;
+use std::io;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+
+use crate::game::cs::{ProtocolState, WorldState};
+use crate::session::{RosterChange, SessionRoster, SessionState, SessionTransition};
+
+/// What happened in the session. State changes that don't map to anything
+/// more meaningful are recorded as plain state changes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "event")]
+pub enum SessionEventKind {
+    LobbyCreated,
+    LobbyOpened,
+    JoinRequested,
+    EnteredClientWorld,
+    LobbyClosed,
+    WentOffline,
+    AwaitingWorldData,
+    EnteredWorld,
+    WorldStateChanged { world_state: u32 },
+    ProtocolStateChanged { protocol_state: u32 },
+    PlayerJoined { steam_id: u64 },
+    PlayerLeft { steam_id: u64 },
+}
+
+impl SessionEventKind {
+    /// Names the changes of a transition. A transition can change both
+    /// states at once, in which case the world state event comes first. The
+    /// first observed state only yields events if it isn't idle.
+    pub fn from_transition(transition: &SessionTransition) -> Vec<Self> {
+        let to = transition.to;
+        let from = transition.from.unwrap_or(SessionState {
+            world_state: Ok(WorldState::Offline),
+            protocol_state: Ok(ProtocolState::Inactive),
+        });
+
+        let mut kinds = vec![];
+        if from.world_state != to.world_state {
+            kinds.push(match to.world_state {
+                Ok(WorldState::Offline) => Self::WentOffline,
+                Ok(WorldState::CreatingLobby) => Self::LobbyCreated,
+                Ok(WorldState::Host) => Self::LobbyOpened,
+                Ok(WorldState::AwaitingJoin) => Self::JoinRequested,
+                Ok(WorldState::Client) => Self::EnteredClientWorld,
+                Ok(WorldState::ClosingLobby) => Self::LobbyClosed,
+                Ok(world_state) => Self::WorldStateChanged { world_state: world_state as u32 },
+                Err(world_state) => Self::WorldStateChanged { world_state },
+            });
+        }
+
+        if from.protocol_state != to.protocol_state {
+            kinds.push(match to.protocol_state {
+                Ok(ProtocolState::AwaitingWorldData) => Self::AwaitingWorldData,
+                Ok(ProtocolState::InWorld) => Self::EnteredWorld,
+                Ok(protocol_state) => Self::ProtocolStateChanged { protocol_state: protocol_state as u32 },
+                Err(protocol_state) => Self::ProtocolStateChanged { protocol_state },
+            });
+        }
+
+        kinds
+    }
+}
+
+impl From<RosterChange> for SessionEventKind {
+    fn from(value: RosterChange) -> Self {
+        match value {
+            RosterChange::Joined(steam_id) => Self::PlayerJoined { steam_id },
+            RosterChange::Left(steam_id) => Self::PlayerLeft { steam_id },
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SessionEventPlayer {
+    pub steam_id: u64,
+    pub session_slot: i32,
+}
+
+/// A single line of the event log.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SessionEvent {
+    /// Milliseconds since the unix epoch.
+    pub timestamp: u64,
+    #[serde(flatten)]
+    pub kind: SessionEventKind,
+    /// Players in the session at the time of the event.
+    pub roster: Vec<SessionEventPlayer>,
+}
+
+impl SessionEvent {
+    pub fn new(kind: SessionEventKind, roster: &SessionRoster, at: SystemTime) -> Self {
+        Self {
+            timestamp: at.duration_since(UNIX_EPOCH)
+                .map(|duration| duration.as_millis() as u64)
+                .unwrap_or_default(),
+            kind,
+            roster: roster.present()
+                .map(|player| SessionEventPlayer {
+                    steam_id: player.steam_id,
+                    session_slot: player.session_slot,
+                })
+                .collect(),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum SessionEventLogError {
+    Io(io::Error),
+    Serialize(serde_json::Error),
+}
+
+/// Writes session events to a JSON-lines file. Once the file grows past the
+/// size limit it's moved aside as `<name>.1.jsonl`, shifting older files
+/// up, and only the configured amount of old files is kept.
+pub struct SessionEventRecorder {
+    directory: PathBuf,
+    name: String,
+    max_file_size: u64,
+    max_files: usize,
+    recent: VecDeque<SessionEvent>,
+}
+
+impl SessionEventRecorder {
+    /// Amount of events kept in memory for the overlay.
+    const RECENT_CAPACITY: usize = 32;
+
+    pub fn new(directory: impl Into<PathBuf>, name: &str, max_file_size: u64, max_files: usize) -> Self {
+        Self {
+            directory: directory.into(),
+            name: name.to_string(),
+            max_file_size,
+            max_files,
+            recent: VecDeque::with_capacity(Self::RECENT_CAPACITY),
+        }
+    }
+
+    pub fn path(&self) -> PathBuf {
+        self.rotated_path(0)
+    }
+
+    /// Path of an old log, 0 being the log that is currently written to.
+    pub fn rotated_path(&self, index: usize) -> PathBuf {
+        match index {
+            0 => self.directory.join(format!("{}.jsonl", self.name)),
+            _ => self.directory.join(format!("{}.{index}.jsonl", self.name)),
+        }
+    }
+
+    pub fn record(&mut self, event: SessionEvent) -> Result<(), SessionEventLogError> {
+        let mut line = serde_json::to_vec(&event)
+            .map_err(SessionEventLogError::Serialize)?;
+        line.push(b'\n');
+
+        if self.recent.len() == Self::RECENT_CAPACITY {
+            self.recent.pop_front();
+        }
+        self.recent.push_back(event);
+
+        let path = self.path();
+        let size = fs::metadata(&path).map(|metadata| metadata.len()).unwrap_or(0);
+        if size > 0 && size + line.len() as u64 > self.max_file_size {
+            self.rotate().map_err(SessionEventLogError::Io)?;
+        }
+
+        fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&path)
+            .and_then(|mut fh| fh.write_all(&line))
+            .map_err(SessionEventLogError::Io)
+    }
+
+    /// Turns a session state transition and the roster changes observed
+    /// alongside it into events and records them.
+    pub fn record_changes(
+        &mut self,
+        transition: Option<&SessionTransition>,
+        roster_changes: &[RosterChange],
+        roster: &SessionRoster,
+        at: SystemTime,
+    ) -> Result<(), SessionEventLogError> {
+        let kinds = transition.map(SessionEventKind::from_transition)
+            .unwrap_or_default()
+            .into_iter()
+            .chain(roster_changes.iter().copied().map(SessionEventKind::from));
+
+        for kind in kinds {
+            self.record(SessionEvent::new(kind, roster, at))?;
+        }
+
+        Ok(())
+    }
+
+    /// Events recorded by this recorder from oldest to newest, bounded to the
+    /// last few.
+    pub fn recent(&self) -> impl DoubleEndedIterator<Item = &SessionEvent> {
+        self.recent.iter()
+    }
+
+    fn rotate(&self) -> io::Result<()> {
+        if self.max_files == 0 {
+            return remove_if_exists(&self.path());
+        }
+
+        remove_if_exists(&self.rotated_path(self.max_files))?;
+        for index in (0..self.max_files).rev() {
+            let from = self.rotated_path(index);
+            if from.exists() {
+                fs::rename(from, self.rotated_path(index + 1))?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+fn remove_if_exists(path: &Path) -> io::Result<()> {
+    match fs::remove_file(path) {
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(()),
+        result => result,
+    }
+}
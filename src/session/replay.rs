@@ -0,0 +1,155 @@
+use std::fs;
+use std::io;
+use std::io::BufRead;
+use std::path::Path;
+
+use crate::session::{SessionEvent, SessionEventKind};
+
+#[derive(Debug)]
+pub enum SessionReplayError {
+    Io(io::Error),
+    /// Line numbers start at 1.
+    Parse { line: usize, error: serde_json::Error },
+}
+
+/// Reads back an event log written by the SessionEventRecorder.
+pub fn read_session_events(path: impl AsRef<Path>) -> Result<Vec<SessionEvent>, SessionReplayError> {
+    let file = fs::File::open(path)
+        .map_err(SessionReplayError::Io)?;
+
+    parse_session_events(io::BufReader::new(file))
+}
+
+/// Parses JSON-lines session events, skipping blank lines.
+pub fn parse_session_events(reader: impl BufRead) -> Result<Vec<SessionEvent>, SessionReplayError> {
+    let mut events = vec![];
+
+    for (index, line) in reader.lines().enumerate() {
+        let line = line.map_err(SessionReplayError::Io)?;
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        let event = serde_json::from_str(&line)
+            .map_err(|error| SessionReplayError::Parse { line: index + 1, error })?;
+        events.push(event);
+    }
+
+    Ok(events)
+}
+
+/// Splits a log into sessions, each starting at a created lobby or a join
+/// request. Events before the first session start are kept as a session of
+/// their own.
+pub fn split_sessions(events: &[SessionEvent]) -> Vec<&[SessionEvent]> {
+    let mut sessions = vec![];
+    let mut start = 0;
+
+    for (index, event) in events.iter().enumerate() {
+        let starts_session = matches!(
+            event.kind,
+            SessionEventKind::LobbyCreated | SessionEventKind::JoinRequested,
+        );
+
+        if starts_session && index > start {
+            sessions.push(&events[start..index]);
+            start = index;
+        }
+    }
+
+    if start < events.len() {
+        sessions.push(&events[start..]);
+    }
+
+    sessions
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SessionEventDiff {
+    Both(SessionEventKind),
+    Left(SessionEventKind),
+    Right(SessionEventKind),
+}
+
+/// Lines up the events of two sessions. Only the kinds of events are
+/// compared, timestamps and rosters are bound to differ between sessions.
+pub fn diff_sessions(left: &[SessionEvent], right: &[SessionEvent]) -> Vec<SessionEventDiff> {
+    // Longest common subsequence table, lengths[i][j] holds the LCS length
+    // of left[i..] and right[j..].
+    let mut lengths = vec![vec![0usize; right.len() + 1]; left.len() + 1];
+    for i in (0..left.len()).rev() {
+        for j in (0..right.len()).rev() {
+            lengths[i][j] = if left[i].kind == right[j].kind {
+                lengths[i + 1][j + 1] + 1
+            } else {
+                lengths[i + 1][j].max(lengths[i][j + 1])
+            };
+        }
+    }
+
+    let mut diff = vec![];
+    let (mut i, mut j) = (0, 0);
+    while i < left.len() && j < right.len() {
+        if left[i].kind == right[j].kind {
+            diff.push(SessionEventDiff::Both(left[i].kind));
+            i += 1;
+            j += 1;
+        } else if lengths[i + 1][j] >= lengths[i][j + 1] {
+            diff.push(SessionEventDiff::Left(left[i].kind));
+            i += 1;
+        } else {
+            diff.push(SessionEventDiff::Right(right[j].kind));
+            j += 1;
+        }
+    }
+
+    diff.extend(left[i..].iter().map(|event| SessionEventDiff::Left(event.kind)));
+    diff.extend(right[j..].iter().map(|event| SessionEventDiff::Right(event.kind)));
+
+    diff
+}
+
+/// Two event logs loaded side by side so a session of each can be diffed.
+/// Sessions are picked by their index within the log.
+#[derive(Debug, Default)]
+pub struct SessionLogComparison {
+    pub left_path: String,
+    pub right_path: String,
+    pub left_session: usize,
+    pub right_session: usize,
+    /// What went wrong the last time the logs were loaded.
+    pub error: Option<SessionReplayError>,
+    left: Vec<SessionEvent>,
+    right: Vec<SessionEvent>,
+}
+
+impl SessionLogComparison {
+    /// Reads both logs and starts over at their first sessions. Neither log
+    /// is replaced unless both could be read.
+    pub fn load(&mut self) -> Result<(), SessionReplayError> {
+        let left = read_session_events(&self.left_path)?;
+        let right = read_session_events(&self.right_path)?;
+
+        self.left = left;
+        self.right = right;
+        self.left_session = 0;
+        self.right_session = 0;
+        Ok(())
+    }
+
+    pub fn left_sessions(&self) -> Vec<&[SessionEvent]> {
+        split_sessions(&self.left)
+    }
+
+    pub fn right_sessions(&self) -> Vec<&[SessionEvent]> {
+        split_sessions(&self.right)
+    }
+
+    /// Diffs the picked sessions, None if either log doesn't have it.
+    pub fn diff(&self) -> Option<Vec<SessionEventDiff>> {
+        let left = *self.left_sessions().get(self.left_session)?;
+        let right = *self.right_sessions().get(self.right_session)?;
+
+        Some(diff_sessions(left, right))
+    }
+}
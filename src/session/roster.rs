@@ -0,0 +1,130 @@
+use std::time::SystemTime;
+
+use crate::game::cs::CSSessionManager;
+
+/// Player as seen in a single observation of the session manager. Players
+/// aren't linked to their characters, the session manager's entries don't
+/// hold anything that is known to identify a ChrIns. The entries are read
+/// through a layout that hasn't been checked against a capture.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ObservedPlayer {
+    pub steam_id: u64,
+    pub session_slot: i32,
+}
+
+impl ObservedPlayer {
+    /// Reads the players from the session manager, None if its player list
+    /// doesn't look like one.
+    pub fn collect(session_manager: &CSSessionManager) -> Option<Vec<Self>> {
+        let players = session_manager.players()?
+            .iter()
+            .map(|entry| Self {
+                steam_id: entry.steam_id,
+                session_slot: entry.session_slot,
+            })
+            .collect();
+
+        Some(players)
+    }
+}
+
+/// A player's stay in our session. The game doesn't keep track of when
+/// players joined or left so those are the times we noticed it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SessionPlayer {
+    pub steam_id: u64,
+    pub session_slot: i32,
+    pub joined_at: SystemTime,
+    pub left_at: Option<SystemTime>,
+}
+
+impl SessionPlayer {
+    pub fn is_present(&self) -> bool {
+        self.left_at.is_none()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RosterChange {
+    Joined(u64),
+    Left(u64),
+}
+
+/// Everyone that has been in the session since the roster was created or
+/// last cleared. Players that rejoin get a new entry.
+#[derive(Default)]
+pub struct SessionRoster {
+    players: Vec<SessionPlayer>,
+    /// Whether the last observation found something other than a player
+    /// list.
+    unreadable: bool,
+}
+
+impl SessionRoster {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Reconciles the roster with the players currently in the session.
+    /// Returns who left and who joined since the last update.
+    pub fn update(&mut self, observed: &[ObservedPlayer], at: SystemTime) -> Vec<RosterChange> {
+        let mut changes = vec![];
+
+        for player in self.players.iter_mut().filter(|player| player.is_present()) {
+            match observed.iter().find(|observed| observed.steam_id == player.steam_id) {
+                Some(observed) => player.session_slot = observed.session_slot,
+                None => {
+                    player.left_at = Some(at);
+                    changes.push(RosterChange::Left(player.steam_id));
+                },
+            }
+        }
+
+        for observed in observed {
+            if self.present().any(|player| player.steam_id == observed.steam_id) {
+                continue;
+            }
+
+            self.players.push(SessionPlayer {
+                steam_id: observed.steam_id,
+                session_slot: observed.session_slot,
+                joined_at: at,
+                left_at: None,
+            });
+            changes.push(RosterChange::Joined(observed.steam_id));
+        }
+
+        changes
+    }
+
+    /// Updates the roster from the session manager. An unreadable player
+    /// list leaves the roster as is.
+    pub fn observe(&mut self, session_manager: &CSSessionManager, at: SystemTime) -> Vec<RosterChange> {
+        let observed = ObservedPlayer::collect(session_manager);
+        self.unreadable = observed.is_none();
+
+        match observed {
+            Some(observed) => self.update(&observed, at),
+            None => vec![],
+        }
+    }
+
+    pub fn is_unreadable(&self) -> bool {
+        self.unreadable
+    }
+
+    /// Every stay in order of joining, including players that have left.
+    pub fn players(&self) -> &[SessionPlayer] {
+        &self.players
+    }
+
+    /// Players that are currently in the session.
+    pub fn present(&self) -> impl Iterator<Item = &SessionPlayer> {
+        self.players.iter().filter(|player| player.is_present())
+    }
+
+    pub fn clear(&mut self) {
+        self.players.clear();
+        self.unreadable = false;
+    }
+}
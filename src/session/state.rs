@@ -0,0 +1,107 @@
+use std::collections::VecDeque;
+use std::fmt;
+use std::time::Instant;
+
+use crate::game::cs::{CSSessionManager, ProtocolState, WorldState};
+
+/// Snapshot of the session manager's states. Values the enums don't know
+/// about are kept around as their raw value.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SessionState {
+    pub world_state: Result<WorldState, u32>,
+    pub protocol_state: Result<ProtocolState, u32>,
+}
+
+impl SessionState {
+    pub fn of(session_manager: &CSSessionManager) -> Self {
+        Self {
+            world_state: session_manager.world_state(),
+            protocol_state: session_manager.protocol_state(),
+        }
+    }
+}
+
+impl fmt::Display for SessionState {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.world_state {
+            Ok(world_state) => write!(f, "{world_state:?}")?,
+            Err(raw) => write!(f, "Unknown({raw})")?,
+        }
+
+        f.write_str(" / ")?;
+
+        match self.protocol_state {
+            Ok(protocol_state) => write!(f, "{protocol_state:?}"),
+            Err(raw) => write!(f, "Unknown({raw})"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SessionTransition {
+    pub at: Instant,
+    /// None for the first state that was observed.
+    pub from: Option<SessionState>,
+    pub to: SessionState,
+}
+
+/// Keeps a bounded history of the session state changes that were observed.
+/// The game doesn't tell us when states change so the tracker has to be fed
+/// every frame.
+pub struct SessionStateTracker {
+    current: Option<SessionState>,
+    history: VecDeque<SessionTransition>,
+    capacity: usize,
+}
+
+impl SessionStateTracker {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            current: None,
+            history: VecDeque::with_capacity(capacity),
+            capacity,
+        }
+    }
+
+    /// Records the state if it differs from the last one observed, evicting
+    /// the oldest transition if the history is full.
+    pub fn observe(&mut self, state: SessionState, at: Instant) -> Option<SessionTransition> {
+        if self.current == Some(state) {
+            return None;
+        }
+
+        let transition = SessionTransition { at, from: self.current, to: state };
+        self.current = Some(state);
+
+        if self.capacity == 0 {
+            return Some(transition);
+        }
+
+        if self.history.len() == self.capacity {
+            self.history.pop_front();
+        }
+        self.history.push_back(transition);
+
+        Some(transition)
+    }
+
+    pub fn current(&self) -> Option<SessionState> {
+        self.current
+    }
+
+    /// Transitions from oldest to newest.
+    pub fn history(&self) -> impl DoubleEndedIterator<Item = &SessionTransition> {
+        self.history.iter()
+    }
+
+    pub fn clear(&mut self) {
+        self.current = None;
+        self.history.clear();
+    }
+}
+
+impl Default for SessionStateTracker {
+    fn default() -> Self {
+        Self::new(64)
+    }
+}
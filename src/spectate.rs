@@ -0,0 +1,178 @@
+use crate::game::cs::{ChrIns, ChrQuery, FieldInsHandle, WorldChrMan, WorldChrManDbg};
+use serde::{Deserialize, Serialize};
+
+use crate::util::input::{KeyBinding, KeyboardState};
+
+/// Hotkeys for spectating, keys left out of a config keep their default.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SpectateKeys {
+    pub toggle: KeyBinding,
+    pub previous: KeyBinding,
+    pub next: KeyBinding,
+    pub ragdoll: KeyBinding,
+}
+
+impl Default for SpectateKeys {
+    /// Home, page up, page down and insert.
+    fn default() -> Self {
+        Self {
+            toggle: KeyBinding::new(0x24),
+            previous: KeyBinding::new(0x21),
+            next: KeyBinding::new(0x22),
+            ragdoll: KeyBinding::new(0x2d),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SpectateAction {
+    Toggle,
+    Previous,
+    Next,
+    /// Toggles the ragdoll of the character being spectated.
+    ToggleRagdoll,
+}
+
+/// Points the camera at other characters by overriding the camera target in
+/// WorldChrManDbg. Characters are cycled through in the order the query
+/// returns them.
+pub struct Spectate {
+    pub keys: SpectateKeys,
+    pub query: ChrQuery,
+    active: bool,
+    target: Option<FieldInsHandle>,
+    /// Override that was in place before spectating, restored when done.
+    saved_override: Option<FieldInsHandle>,
+}
+
+impl Spectate {
+    pub fn new(keys: SpectateKeys) -> Self {
+        Self {
+            keys,
+            query: ChrQuery::new(),
+            active: false,
+            target: None,
+            saved_override: None,
+        }
+    }
+
+    pub fn is_active(&self) -> bool {
+        self.active
+    }
+
+    /// Character that is being spectated.
+    pub fn target(&self) -> Option<FieldInsHandle> {
+        self.target
+    }
+
+    /// Checks the hotkeys for actions to take.
+    pub fn actions(&self, keyboard: &KeyboardState) -> Vec<SpectateAction> {
+        let keys = self.keys;
+
+        [
+            (keys.toggle, SpectateAction::Toggle),
+            (keys.previous, SpectateAction::Previous),
+            (keys.next, SpectateAction::Next),
+            (keys.ragdoll, SpectateAction::ToggleRagdoll),
+        ].into_iter()
+            .filter(|(binding, _)| binding.is_pressed(keyboard))
+            .map(|(_, action)| action)
+            .collect()
+    }
+
+    /// Characters that can be spectated right now.
+    pub fn candidates<'a>(&self, world_chr_man: &WorldChrMan<'a>) -> Vec<&'a ChrIns<'a>> {
+        world_chr_man.query(self.query)
+            .map(|entry| entry.chr_ins)
+            .collect()
+    }
+
+    pub fn handle(&mut self, action: SpectateAction, world_chr_man: &WorldChrMan, dbg: &mut WorldChrManDbg) {
+        match action {
+            SpectateAction::Toggle if self.active => self.disable(world_chr_man, dbg),
+            SpectateAction::Toggle => self.enable(world_chr_man, dbg),
+            SpectateAction::Previous => self.cycle(world_chr_man, dbg, -1),
+            SpectateAction::Next => self.cycle(world_chr_man, dbg, 1),
+            SpectateAction::ToggleRagdoll => self.toggle_ragdoll(world_chr_man),
+        }
+    }
+
+    /// Starts spectating the first candidate.
+    pub fn enable(&mut self, world_chr_man: &WorldChrMan, dbg: &mut WorldChrManDbg) {
+        if self.active {
+            return;
+        }
+
+        self.active = true;
+        self.saved_override = dbg.cam_override().map(|chr_ins| chr_ins.field_ins_handle);
+        self.target = None;
+        self.cycle(world_chr_man, dbg, 0);
+    }
+
+    /// Hands the camera back to whoever had it before spectating. If that
+    /// character has despawned in the meantime the camera goes back to the
+    /// main player.
+    pub fn disable(&mut self, world_chr_man: &WorldChrMan, dbg: &mut WorldChrManDbg) {
+        if !self.active {
+            return;
+        }
+
+        self.active = false;
+        self.target = None;
+
+        let saved_override = self.saved_override.take()
+            .and_then(|handle| world_chr_man.find_by_handle(handle))
+            .map(|entry| entry.chr_ins);
+        dbg.set_cam_override(saved_override);
+    }
+
+    /// Moves offset characters along the candidates, wrapping around at the
+    /// ends. Starts from the first candidate if the target is gone.
+    pub fn cycle(&mut self, world_chr_man: &WorldChrMan, dbg: &mut WorldChrManDbg, offset: isize) {
+        if !self.active {
+            return;
+        }
+
+        let candidates = self.candidates(world_chr_man);
+        let current = self.target
+            .and_then(|target| candidates.iter().position(|chr_ins| chr_ins.field_ins_handle == target));
+
+        let next = match current {
+            _ if candidates.is_empty() => None,
+            Some(current) => Some((current as isize + offset).rem_euclid(candidates.len() as isize) as usize),
+            None => Some(0),
+        };
+
+        let chr_ins = next.map(|index| candidates[index]);
+        self.target = chr_ins.map(|chr_ins| chr_ins.field_ins_handle);
+        dbg.set_cam_override(chr_ins);
+    }
+
+    /// Keeps the camera on the target. Moves on to the next candidate once the
+    /// target despawns.
+    pub fn update(&mut self, world_chr_man: &WorldChrMan, dbg: &mut WorldChrManDbg) {
+        if !self.active {
+            return;
+        }
+
+        let target = self.target
+            .and_then(|target| world_chr_man.find_by_handle(target))
+            .map(|entry| entry.chr_ins);
+
+        match target {
+            Some(chr_ins) => dbg.set_cam_override(Some(chr_ins)),
+            None => self.cycle(world_chr_man, dbg, 0),
+        }
+    }
+
+    fn toggle_ragdoll(&self, world_chr_man: &WorldChrMan) {
+        let Some(entry) = self.target.and_then(|target| world_chr_man.find_by_handle(target)) else {
+            return;
+        };
+
+        if let Some(chr_ctrl) = unsafe { entry.chr_ins.chr_ctrl_mut() } {
+            chr_ctrl.toggle_ragdoll();
+        }
+    }
+}
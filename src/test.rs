@@ -1,5 +1,36 @@
-use crate::game::dl::DLWString;
-use crate::game::fd4::{FD4BasicHashString, FD4ResCap, FD4ResCapHolder, FfxRepositoryImp, ResRepository};
+use std::ptr::NonNull;
+use std::time::{Duration, Instant, SystemTime};
+
+use zerocopy::{FromBytes, FromZeroes};
+
+use crate::game::cs::{
+    CSCam, CSEzSelectBotString, CSFile, CSSessionManager, CSSessionManagerPlayerEntry, ChrCtrl, ChrDbgFlags, ChrDbgFlagsError, ChrDbgFlagsRef, ChrIns, ChrInsModuleContainer, ChrPhysicsModule, ChrQuery, ChrSetEntry, ChrSetOrigin, FieldInsHandle, FieldInsHandleParseError,
+    FieldInsSelector, MapId, MapIdParseError, ProtocolState, RagdollState, WorldChrMan, WorldChrManDbg, WorldState,
+};
+use crate::game::dl::{DLAllocator, DLAllocatorRef, DLAllocatorVMT, DLPlainLightMutex, DLString, DLStringError, DLWString};
+use crate::game::fd4::{
+    hash_resource_name, normalize_resource_name, FD4BasicHashString, FD4ResCap, FD4ResCapHolder, FfxRepositoryImp,
+};
+use crate::game::matrix::{Matrix4X4, Vector2, Vector4};
+use crate::free_camera::{CameraUpdateConfig, CameraUpdateError, FreeCamera, FreeCameraInput, FreeCameraKeys};
+use crate::spectate::{Spectate, SpectateAction, SpectateKeys};
+use crate::config::{Config, ConfigError, ConfigWatcher};
+use crate::export::{write_records, ExportFormat};
+use crate::logging::LogLevel;
+use crate::util::input::{KeyboardState, KEY_ALT, KEY_CONTROL};
+use crate::game::stl::{StdMap, StdSet};
+use crate::game::world_area_time::{
+    TimeOfDay, TimeOfDayError, WorldAreaTime, WorldAreaTimeClock,
+};
+use crate::session::{
+    diff_sessions, parse_session_events, read_session_events, split_sessions, SessionLogComparison, ObservedPlayer, RosterChange,
+    SessionEvent, SessionEventDiff, SessionEventKind, SessionEventRecorder, SessionReplayError, SessionRoster,
+    SessionState, SessionStateTracker, SessionTransition,
+};
+
+mod fixture;
+
+use fixture::{AllocatorCall, FileOperatorCall};
 
 #[repr(align(8))]
 struct AlignedBuffer<const T: usize>([u8; T]);
@@ -37,7 +68,54 @@ fn test_fd4basichashstring() {
 
     assert_eq!(string.unk1, 0x01);
     assert_eq!(string.hash, 0x392fa297);
-    assert!(!string.needs_hashing);
+    assert_eq!(string.needs_hashing, 0);
+
+    // The string has been hashed already so this must not touch the heap
+    // allocated name, which doesn't exist in the fixture.
+    assert_eq!(string.computed_hash(), 0x392fa297);
+}
+
+#[test]
+fn test_hash_resource_name() {
+    // Plain FNV-1a test vectors, the UTF-16 code units of ASCII names match
+    // the bytes.
+    assert_eq!(hash_resource_name(""), 0x811c9dc5);
+    assert_eq!(hash_resource_name("a"), 0xe40c292c);
+    assert_eq!(hash_resource_name("foobar"), 0xbf9cf968);
+
+    // Names are case insensitive and don't care about separators
+    assert_eq!(hash_resource_name("FOOBAR"), 0xbf9cf968);
+    assert_eq!(
+        hash_resource_name("N:\\GR\\data\\Model\\chr\\c0000.flver"),
+        hash_resource_name("n:/gr/data/model/chr/c0000.flver"),
+    );
+    assert_eq!(normalize_resource_name("Map\\M60_42_36_00"), "map/m60_42_36_00");
+
+    // Non-ASCII characters are hashed as their UTF-16 code units
+    assert_ne!(hash_resource_name("\u{00e9}"), hash_resource_name("\u{00c9}"));
+}
+
+#[test]
+fn test_fd4basichashstring_construction() {
+    let hash_string = FD4BasicHashString::from_name("C0000");
+    assert_eq!(hash_string.hash, hash_resource_name("c0000"));
+    assert_eq!(hash_string.needs_hashing, 0);
+    assert_eq!(hash_string.computed_hash(), hash_string.hash);
+    assert_eq!(hash_string.string, "C0000");
+
+    // Too long for the inline buffer, so only the hash is kept
+    let hash_string = FD4BasicHashString::from_name("m60_42_36_00");
+    assert_eq!(hash_string.hash, hash_resource_name("m60_42_36_00"));
+    assert!(hash_string.string.is_empty());
+
+    let mut unhashed: FD4BasicHashString = unsafe { std::mem::zeroed() };
+    unhashed.string = fixture::dl_wstring("c2010");
+    unhashed.needs_hashing = 1;
+    assert_eq!(unhashed.computed_hash(), hash_resource_name("c2010"));
+
+    unhashed.rehash();
+    assert_eq!(unhashed.needs_hashing, 0);
+    assert_eq!(unhashed.hash, hash_resource_name("c2010"));
 }
 
 #[test]
@@ -57,10 +135,10 @@ fn test_fd4rescap() {
         &*(DATA.0.as_ptr() as *const FD4ResCap<()>)
     };
 
-    assert_eq!(res_cap.vftable, 0x142b19538);
-    assert_eq!(res_cap.owning_repository as usize, 0x7ff49e1814d0);
-    assert_eq!(res_cap.next_item as usize, 0x7ff49e310f60);
-    assert_eq!(res_cap.reference_count, 0x1);
+    assert_eq!(res_cap.header.vftable, 0x142b19538);
+    assert_eq!(res_cap.header.owning_repository as *const _ as usize, 0x7ff49e1814d0);
+    assert_eq!(res_cap.header.next_item as usize, 0x7ff49e310f60);
+    assert_eq!(res_cap.header.reference_count, 0x1);
 }
 
 #[test]
@@ -77,10 +155,10 @@ fn test_resrepository() {
     };
 
     assert_eq!(holder.vftable, 0x143237600);
-    assert_eq!(holder.allocator, 0x143acefb8);
+    assert_eq!(holder.allocator.as_raw(), 0x143acefb8);
     assert_eq!(holder.owning_repository, 0x7ff49e5a9c20);
     assert_eq!(holder.capacity, 0x26f5);
-    assert_eq!(holder.map as usize, 0x7ff3f6191ce0);
+    assert_eq!(holder.map as *const _ as usize, 0x7ff3f6191ce0);
 }
 
 #[test]
@@ -106,5 +184,1688 @@ fn test_ffxrepository() {
         &*(DATA.0.as_ptr() as *const FfxRepositoryImp)
     };
 
-    println!("{:#?}", repository);
+    assert_eq!(repository.repository_res_cap.header.vftable, 0x142b4c458);
+    assert_eq!(repository.repository_res_cap.header.name.string.to_string(), "FFX");
+    assert_eq!(repository.map.vftable, 0x143237600);
+    assert_eq!(repository.map.capacity, 0x138b);
+
+    // The capture was taken before the game hashed the name.
+    let name = &repository.repository_res_cap.header.name;
+    assert_eq!(name.hash, 0);
+    assert_eq!(name.needs_hashing, 1);
+    assert_eq!(name.computed_hash(), hash_resource_name("FFX"));
+}
+
+#[test]
+fn test_std_map_iteration() {
+    let map: StdMap<u32, u64> = fixture::std_map(
+        (0..100).map(|i| (i * 3, i as u64 * 10)).collect()
+    );
+
+    assert_eq!(map.len(), 100);
+    assert_eq!(
+        map.keys().copied().collect::<Vec<_>>(),
+        (0..100).map(|i| i * 3).collect::<Vec<_>>(),
+    );
+    assert_eq!(map.get(&42), Some(&140));
+    assert_eq!(map.get(&43), None);
+}
+
+#[test]
+fn test_std_set_iteration() {
+    for size in 0..16 {
+        let set: StdSet<u64> = fixture::std_tree((0..size).collect());
+
+        assert_eq!(set.iter().copied().collect::<Vec<_>>(), (0..size).collect::<Vec<_>>());
+        assert!((0..size).all(|i| set.contains(&i)));
+        assert!(!set.contains(&size));
+    }
+}
+
+#[test]
+fn test_std_tree_empty() {
+    let set: StdSet<u64> = fixture::std_tree(vec![]);
+    assert!(set.is_empty());
+    assert_eq!(set.iter().count(), 0);
+
+    let zeroed: StdSet<u64> = unsafe { std::mem::zeroed() };
+    assert!(zeroed.is_empty());
+    assert_eq!(zeroed.iter().count(), 0);
+}
+
+#[test]
+fn test_std_list_iteration() {
+    let list = fixture::std_list(vec![5u32, 3, 8, 1]);
+    assert_eq!(list.len(), 4);
+    assert_eq!(list.iter().copied().collect::<Vec<_>>(), vec![5, 3, 8, 1]);
+
+    let empty = fixture::std_list::<u32>(vec![]);
+    assert_eq!(empty.iter().count(), 0);
+}
+
+#[test]
+fn test_std_vector() {
+    let mut values = Vec::with_capacity(10);
+    values.extend([1u16, 2, 3]);
+
+    let vector = fixture::std_vector(values);
+    assert_eq!(vector.len(), 3);
+    assert_eq!(vector.capacity(), 10);
+    assert_eq!(vector.as_slice(), &[1, 2, 3]);
+
+    let zeroed: crate::game::stl::StdVector<u16> = unsafe { std::mem::zeroed() };
+    assert!(zeroed.is_empty());
+    assert_eq!(zeroed.iter().count(), 0);
+}
+
+#[test]
+fn test_std_unordered_map() {
+    let map = fixture::std_unordered_map(vec![(7u32, 'a'), (1, 'b'), (4, 'c')], 8);
+    assert_eq!(map.len(), 3);
+    assert_eq!(map.bucket_count(), 8);
+    assert_eq!(map.iter().map(|p| p.first).collect::<Vec<_>>(), vec![7, 1, 4]);
+    assert_eq!(map.get(&1), Some(&'b'));
+    assert_eq!(map.get(&2), None);
+}
+
+#[test]
+fn test_fd4rescapholder_iteration() {
+    let names = [
+        "c0000", "c2010", "c3000", "c4290", "am_m_1000", "bd_f_1620",
+        "wp_a_0210", "AEG099_001", "m60_42_36_00", "c0000_a00_lo",
+    ];
+    let holder = unsafe { &*fixture::fd4_res_cap_holder(4, &names) };
+
+    let mut found = holder.iter()
+        .map(|res_cap| res_cap.header.name.string.to_string())
+        .collect::<Vec<_>>();
+    found.sort();
+
+    let mut expected = names.map(str::to_string).to_vec();
+    expected.sort();
+    assert_eq!(found, expected);
+
+    let stats = holder.stats();
+    assert_eq!(stats.capacity, 4);
+    assert_eq!(stats.entries, names.len() as u32);
+    assert!(stats.occupied_slots <= 4);
+    assert!(stats.longest_chain >= 3);
+    assert_eq!(stats.load_factor(), 2.5);
+}
+
+#[test]
+fn test_dlplainlightmutex_guard() {
+    let mutex = unsafe { &*fixture::alloc_zeroed::<DLPlainLightMutex>() };
+
+    let guard = mutex.lock();
+    assert_eq!(fixture::lock_depth(mutex), 1);
+
+    let recursive = mutex.try_lock().expect("Critical sections are recursive");
+    assert_eq!(fixture::lock_depth(mutex), 2);
+
+    drop(recursive);
+    drop(guard);
+    assert_eq!(fixture::lock_depth(mutex), 0);
+}
+
+#[test]
+fn test_fd4rescapholder_iter_locked() {
+    let holder = unsafe { &*fixture::fd4_res_cap_holder(4, &["c0000", "c2010", "c3000"]) };
+    let mutex = unsafe { &*fixture::alloc_zeroed::<DLPlainLightMutex>() };
+
+    let mut iter = holder.iter_locked(mutex);
+    assert_eq!(fixture::lock_depth(mutex), 1);
+    assert!(iter.next().is_some());
+    assert_eq!(iter.by_ref().count(), 2);
+
+    // Exhausting the iterator doesn't release the lock, dropping it does.
+    assert_eq!(fixture::lock_depth(mutex), 1);
+    drop(iter);
+    assert_eq!(fixture::lock_depth(mutex), 0);
+}
+
+#[test]
+fn test_csfilerepository_iter() {
+    let repository = fixture::cs_file_repository(&["c0000", "c2010"], &["m60_42_36_00"]);
+
+    let mut iter = repository.iter();
+    assert_eq!(fixture::lock_depth(&repository.mutexes[0].mutex), 1);
+    assert_eq!(fixture::lock_depth(&repository.mutexes[1].mutex), 0);
+
+    let mut found = iter.by_ref()
+        .map(|file_cap| file_cap.header.name.string.to_string())
+        .collect::<Vec<_>>();
+    found.sort();
+    assert_eq!(found, vec!["c0000", "c2010", "m60_42_36_00"]);
+
+    drop(iter);
+    assert_eq!(fixture::lock_depth(&repository.mutexes[0].mutex), 0);
+}
+
+#[test]
+fn test_fd4rescapholder_get() {
+    let names = ["c0000", "c2010", "c3000", "c4290", "am_m_1000", "bd_f_1620"];
+    let holder = unsafe { &*fixture::fd4_res_cap_holder(3, &names) };
+
+    for name in names {
+        let res_cap = holder.get(name).expect("Could not find inserted ResCap");
+        assert_eq!(res_cap.header.name.string.to_string(), name);
+        assert_eq!(res_cap.header.name.hash, hash_resource_name(name));
+    }
+
+    assert!(holder.get("c9999").is_none());
+    assert!(holder.get("C4290").is_some());
+}
+
+#[test]
+fn test_fd4rescapholder_empty() {
+    let holder = unsafe { &*fixture::fd4_res_cap_holder(16, &[]) };
+    assert_eq!(holder.iter().count(), 0);
+    assert!(holder.get("c0000").is_none());
+    assert_eq!(holder.stats().occupied_slots, 0);
+
+    let unallocated: FD4ResCapHolder<()> = unsafe { std::mem::zeroed() };
+    assert_eq!(unallocated.iter().count(), 0);
+    assert!(unallocated.get("c0000").is_none());
+}
+
+#[test]
+fn test_dlwstring_inline() {
+    let allocator = fixture::TestAllocator::default();
+
+    let mut string = DLWString::from_str_in("c0000", &allocator).unwrap();
+    assert!(string.is_inline());
+    assert_eq!(string.length, 5);
+    assert_eq!(string.capacity, 7);
+    assert_eq!(string, "c0000");
+    assert_eq!(string.to_string(), "c0000");
+    assert_eq!(allocator.live_allocations(), 0);
+
+    // Seven units and a terminator is the most the inline buffer can hold
+    string.set("c0000_0", &allocator).unwrap();
+    assert!(string.is_inline());
+    assert_eq!(string, "c0000_0");
+    assert_eq!(allocator.live_allocations(), 0);
+
+    assert_eq!(
+        format!("{:?}", string),
+        "DLBasicString { value: \"c0000_0\", length: 7, capacity: 7 }",
+    );
+}
+
+#[test]
+fn test_dlwstring_heap() {
+    let allocator = fixture::TestAllocator::default();
+
+    let mut string = DLWString::from_str_in("c0000_00", &allocator).unwrap();
+    assert!(!string.is_inline());
+    assert_eq!(string.length, 8);
+    assert_eq!(string.capacity, 8);
+    assert_eq!(string, "c0000_00");
+    assert_eq!(allocator.live_allocations(), 1);
+
+    // Shorter strings reuse the existing buffer
+    string.set("c2010", &allocator).unwrap();
+    assert!(!string.is_inline());
+    assert_eq!(string, "c2010");
+    assert_eq!(string.capacity, 8);
+    assert_eq!(allocator.live_allocations(), 1);
+
+    // Longer ones get a new buffer
+    string.set("m60_42_36_00_c4290", &allocator).unwrap();
+    assert_eq!(string, "m60_42_36_00_c4290");
+    assert_eq!(string.capacity, 18);
+    assert_eq!(allocator.live_allocations(), 1);
+
+    string.free(&allocator);
+    assert!(string.is_inline());
+    assert!(string.is_empty());
+    assert_eq!(allocator.live_allocations(), 0);
+}
+
+#[test]
+fn test_dlwstring_set_zeroed() {
+    let allocator = fixture::TestAllocator::default();
+
+    // Short values fit inline even though a zeroed string claims no capacity
+    let mut string = DLWString::new_zeroed();
+    string.set("c0", &allocator).unwrap();
+    assert!(string.is_inline());
+    assert_eq!(string, "c0");
+    assert_eq!(string.capacity, 7);
+    assert_eq!(allocator.live_allocations(), 0);
+
+    let mut string = DLWString::new_zeroed();
+    string.capacity = 3;
+    string.set("c0000", &allocator).unwrap();
+    assert!(string.is_inline());
+    assert_eq!(string, "c0000");
+    assert_eq!(allocator.live_allocations(), 0);
+
+    // Long values end up on the heap and get released again
+    let mut string = DLWString::new_zeroed();
+    string.set("m60_42_36_00", &allocator).unwrap();
+    assert!(!string.is_inline());
+    assert_eq!(string, "m60_42_36_00");
+    assert_eq!(string.capacity, 12);
+    assert_eq!(allocator.live_allocations(), 1);
+
+    string.free(&allocator);
+    assert_eq!(allocator.live_allocations(), 0);
+}
+
+#[test]
+fn test_dlstring_inline_and_heap() {
+    let allocator = fixture::TestAllocator::default();
+
+    let mut string = DLString::from_str_in("regulation.bin", &allocator).unwrap();
+    assert!(string.is_inline());
+    assert_eq!(string, "regulation.bin");
+
+    string.set("data0:/regulation.bin", &allocator).unwrap();
+    assert!(!string.is_inline());
+    assert_eq!(string.to_string(), "data0:/regulation.bin");
+
+    string.free(&allocator);
+    assert_eq!(allocator.live_allocations(), 0);
+}
+
+#[test]
+fn test_dlwstring_decoding_errors() {
+    // Lone surrogate
+    const DATA: AlignedBuffer<32> = AlignedBuffer([
+        0x63, 0x00, 0x00, 0xd8, 0x30, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        0x00, 0x00, 0x00, 0x00, 0x03, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        0x07, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00
+    ]);
+
+    let string: &DLWString = unsafe { &*(DATA.0.as_ptr() as *const DLWString)};
+    assert!(matches!(string.try_to_string(), Err(DLStringError::InvalidUtf16(_))));
+    assert_eq!(string.to_string(), "c\u{fffd}0");
+}
+
+#[test]
+fn test_dlallocator_queries() {
+    let mock = fixture::MockDLAllocator::new();
+    let allocator = &mock.base;
+
+    assert_eq!(allocator.allocator_id(), 0x1337);
+    assert_eq!(allocator.heap_capacity(), fixture::MockDLAllocator::HEAP_CAPACITY);
+    assert_eq!(allocator.backing_heap_capacity(), fixture::MockDLAllocator::HEAP_CAPACITY);
+    assert_eq!(allocator.heap_allocation_count(), 0);
+
+    let ptr = crate::game::dl::GameAllocator::allocate(allocator, 0x30, 0x10);
+    assert_eq!(ptr as usize % 0x10, 0);
+    assert_eq!(allocator.heap_size(), 0x30);
+    assert_eq!(allocator.heap_allocation_count(), 1);
+    assert_eq!(allocator.allocation_size(ptr), 0x30);
+
+    let ptr = unsafe { allocator.reallocate_aligned(ptr, 0x60, 0x10) };
+    assert_eq!(allocator.allocation_size(ptr), 0x60);
+    unsafe { crate::game::dl::GameAllocator::deallocate(allocator, ptr) };
+
+    assert_eq!(allocator.heap_allocation_count(), 0);
+    assert_eq!(mock.take_calls(), vec![
+        AllocatorCall::Allocate(0x30, 0x10),
+        AllocatorCall::Reallocate(0x60, 0x10),
+        AllocatorCall::Deallocate,
+    ]);
+
+    unsafe { allocator.destructor(false) };
+    assert_eq!(mock.take_calls(), vec![AllocatorCall::Destroy(false)]);
+}
+
+#[test]
+fn test_dlallocator_vftable_slots() {
+    let mock = fixture::MockDLAllocator::new();
+    let vftable = mock.base.vftable;
+
+    let allocate_aligned: extern "C" fn(*const DLAllocator, usize, usize) -> *mut u8 =
+        unsafe { vftable.allocate_aligned() };
+    let deallocate: extern "C" fn(*const DLAllocator, *mut u8) = unsafe { vftable.deallocate() };
+
+    unsafe {
+        assert_eq!(vftable.allocator_id() as usize, vftable.slot(1));
+        assert_eq!(vftable.heap_capacity() as usize, vftable.slot(4));
+        assert_eq!(vftable.allocation_size() as usize, vftable.slot(8));
+        assert_eq!(vftable.allocate() as usize, vftable.slot(9));
+        assert_eq!(allocate_aligned as usize, vftable.slot(10));
+        assert_eq!(vftable.reallocate_aligned() as usize, vftable.slot(12));
+        assert_eq!(deallocate as usize, vftable.slot(13));
+    }
+
+    let ptr = mock.base.allocate(0x20);
+    assert_eq!(mock.base.allocation_size(ptr), 0x20);
+    unsafe { mock.base.deallocate(ptr) };
+    assert_eq!(mock.take_calls(), vec![
+        AllocatorCall::Allocate(0x20, 8),
+        AllocatorCall::Deallocate,
+    ]);
+}
+
+#[test]
+fn test_dlallocator_string_calls() {
+    let mock = fixture::MockDLAllocator::new();
+    let allocator = &mock.base;
+
+    let mut string = DLWString::from_str_in("c0000", allocator).unwrap();
+    assert!(mock.take_calls().is_empty());
+
+    string.set("m60_42_36_00", allocator).unwrap();
+    assert_eq!(mock.take_calls(), vec![AllocatorCall::Allocate(26, 2)]);
+
+    string.set("m60_42_36_00_c4290", allocator).unwrap();
+    assert_eq!(mock.take_calls(), vec![
+        AllocatorCall::Allocate(38, 2),
+        AllocatorCall::Deallocate,
+    ]);
+
+    string.free(allocator);
+    assert_eq!(mock.take_calls(), vec![AllocatorCall::Deallocate]);
+    assert_eq!(allocator.heap_allocation_count(), 0);
+}
+
+#[test]
+fn test_dlallocator_rust_allocator() {
+    let mock = fixture::MockDLAllocator::new();
+
+    let mut vector = allocator_api2::vec::Vec::new_in(&mock.base);
+    vector.push(0x1u64);
+    assert_eq!(mock.take_calls(), vec![AllocatorCall::Allocate(32, 8)]);
+
+    vector.extend([2, 3, 4, 5]);
+    assert_eq!(mock.take_calls(), vec![AllocatorCall::Reallocate(64, 8)]);
+    assert_eq!(vector.as_slice(), &[1, 2, 3, 4, 5]);
+
+    drop(vector);
+    assert_eq!(mock.take_calls(), vec![AllocatorCall::Deallocate]);
+    assert_eq!(mock.base.heap_allocation_count(), 0);
+}
+
+#[test]
+fn test_ezselectbotstring_set() {
+    let mock = fixture::MockDLAllocator::new();
+
+    let mut property: CSEzSelectBotString = unsafe { std::mem::zeroed() };
+    property.string = DLWString::new();
+    assert!(matches!(property.set("Host"), Err(DLStringError::MissingAllocator)));
+
+    property.allocator = DLAllocatorRef::new(&mock.base);
+    property.set("Join session").unwrap();
+    assert_eq!(property.string, "Join session");
+    assert_eq!(mock.take_calls(), vec![AllocatorCall::Allocate(26, 2)]);
+
+    property.string.free(&mock.base);
+}
+
+#[test]
+fn test_dlfileoperator_virtual_calls() {
+    let file = fixture::mock_cs_file();
+    let this = file as *const CSFile as usize;
+    fixture::take_file_operator_calls();
+
+    assert_eq!(file.get_runtime_class() as usize, fixture::MOCK_RUNTIME_CLASS);
+
+    let name = FD4BasicHashString::from_name("c0000");
+    assert_eq!(file.get_repository_1_resource(&name) as usize, &name as *const _ as usize);
+
+    let res_cap = fixture::fd4_res_cap("c0000");
+    unsafe {
+        file.unk3(&name, res_cap, 0x10);
+        file.destructor(1);
+    }
+
+    assert_eq!(fixture::take_file_operator_calls(), vec![
+        (this, FileOperatorCall::GetRuntimeClass),
+        (this, FileOperatorCall::GetRepository1Resource(hash_resource_name("c0000"))),
+        (this, FileOperatorCall::Unk3(hash_resource_name("c0000"), res_cap as usize, 0x10)),
+        (this, FileOperatorCall::Destructor(1)),
+    ]);
+}
+
+#[test]
+#[should_panic(expected = "Called into a null DLAllocatorVMT")]
+fn test_null_vftable_call() {
+    let allocator = DLAllocator { vftable: DLAllocatorVMT::from_raw(0) };
+    allocator.allocator_id();
+}
+
+const LIMGRAVE: MapId = MapId::new(60, 42, 36, 0);
+const STORMVEIL: MapId = MapId::new(10, 0, 0, 0);
+
+/// Player 1 at the origin. Character 2 is tracked by two sets, character 3
+/// lives in a WorldBlockChr and character 4 is far away.
+fn populated_world_chr_man<'a>() -> &'a WorldChrMan<'a> {
+    let world_chr_man = fixture::world_chr_man();
+
+    let player = fixture::chr_ins(1, LIMGRAVE, Vector4::new(0.0, 0.0, 0.0));
+    let second = fixture::chr_ins(2, LIMGRAVE, Vector4::new(3.0, 4.0, 0.0));
+    let third = fixture::chr_ins(3, STORMVEIL, Vector4::new(0.0, 0.0, 20.0));
+    let fourth = fixture::chr_ins(4, LIMGRAVE, Vector4::new(300.0, 0.0, 0.0));
+
+    world_chr_man.main_player = player;
+    fixture::fill_chr_set(&mut world_chr_man.chr_set_1, &[player, std::ptr::null_mut(), second]);
+    fixture::fill_chr_set(&mut world_chr_man.open_field_chr_set.base, &[second, fourth]);
+    fixture::fill_chr_set(&mut world_chr_man.world_block_chr[5].chr_set1, &[third]);
+
+    world_chr_man
+}
+
+#[test]
+fn test_worldchrman_characters() {
+    let world_chr_man = populated_world_chr_man();
+
+    let characters = world_chr_man.characters()
+        .map(|entry| (entry.chr_ins.field_ins_handle.instance_id, entry.origin))
+        .collect::<Vec<_>>();
+
+    assert_eq!(characters, vec![
+        (1, ChrSetOrigin::MainPlayer),
+        (2, ChrSetOrigin::ChrSet1),
+        (4, ChrSetOrigin::OpenField),
+        (3, ChrSetOrigin::WorldBlock(5)),
+    ]);
+
+    let found = world_chr_man.find_by_handle(fixture::chr_handle(3)).unwrap();
+    assert_eq!(found.origin, ChrSetOrigin::WorldBlock(5));
+    assert!(world_chr_man.find_by_handle(fixture::chr_handle(5)).is_none());
+}
+
+#[test]
+fn test_worldchrman_query() {
+    let world_chr_man = populated_world_chr_man();
+    let handles = |query: ChrQuery| world_chr_man.query(query)
+        .map(|entry| entry.chr_ins.field_ins_handle.instance_id)
+        .collect::<Vec<_>>();
+
+    assert_eq!(handles(ChrQuery::new().map_id(LIMGRAVE)), vec![1, 2, 4]);
+    assert_eq!(handles(ChrQuery::new().origin(ChrSetOrigin::OpenField)), vec![4]);
+    assert_eq!(handles(ChrQuery::new().max_distance(5.0)), vec![1, 2]);
+    assert_eq!(handles(ChrQuery::new().max_distance(25.0).map_id(LIMGRAVE)), vec![1, 2]);
+    assert!(handles(ChrQuery::new().origin(ChrSetOrigin::ChrSet4)).is_empty());
+}
+
+#[test]
+fn test_worldchrman_query_without_player() {
+    let world_chr_man = fixture::world_chr_man();
+    let chr_ins = fixture::chr_ins(7, MapId::NONE, Vector4::default());
+    fixture::fill_chr_set(&mut world_chr_man.chr_set_2, &[chr_ins]);
+
+    assert!(world_chr_man.main_player().is_none());
+    assert_eq!(world_chr_man.characters().count(), 1);
+    assert_eq!(world_chr_man.query(ChrQuery::new().max_distance(1000.0)).count(), 0);
+}
+
+#[test]
+fn test_chrset_iteration() {
+    let world_chr_man = fixture::world_chr_man();
+    let characters = [
+        std::ptr::null_mut(),
+        fixture::chr_ins(1, MapId::NONE, Vector4::default()),
+        std::ptr::null_mut(),
+        fixture::chr_ins(2, MapId::NONE, Vector4::default()),
+        fixture::chr_ins(3, MapId::NONE, Vector4::default()),
+    ];
+    fixture::fill_chr_set(&mut world_chr_man.chr_set_1, &characters);
+
+    let chr_set = &mut world_chr_man.chr_set_1;
+    chr_set.count = 3;
+    unsafe { (*(chr_set.entries as *mut ChrSetEntry).add(3)).unk8 = 0x20 };
+
+    let elements = chr_set.character_iter()
+        .map(|element| (element.index, element.chr_ins.field_ins_handle.instance_id, element.unk8))
+        .collect::<Vec<_>>();
+    assert_eq!(elements, vec![(1, 1, 0), (3, 2, 0x20), (4, 3, 0)]);
+
+    // Iteration ends after count characters even if there are more entries.
+    chr_set.count = 2;
+    assert_eq!(chr_set.character_iter().count(), 2);
+
+    // The entries beyond capacity are never touched.
+    chr_set.count = 3;
+    chr_set.capacity = 4;
+    assert_eq!(chr_set.character_iter().count(), 2);
+
+    chr_set.count = -1;
+    assert_eq!(chr_set.character_iter().count(), 0);
+}
+
+#[test]
+fn test_chrset_iteration_empty() {
+    let world_chr_man = fixture::world_chr_man();
+
+    // Counts without an entry array are ignored.
+    world_chr_man.chr_set_3.count = 5;
+    world_chr_man.chr_set_3.capacity = 5;
+    assert!(world_chr_man.chr_set_3.entries().is_empty());
+    assert_eq!(world_chr_man.chr_set_3.character_iter().count(), 0);
+}
+
+#[test]
+fn test_openfieldchrset_lists() {
+    let world_chr_man = fixture::world_chr_man();
+    let open_field_chr_set = &mut world_chr_man.open_field_chr_set;
+
+    open_field_chr_set.list1[4].chr_ins = fixture::chr_ins(7, MapId::NONE, Vector4::default());
+    open_field_chr_set.list1[1499].chr_ins = fixture::chr_ins(8, MapId::NONE, Vector4::default());
+    open_field_chr_set.list1[1499].unk0 = 0x10;
+    open_field_chr_set.list2[2].unkc = 1;
+
+    let list1 = open_field_chr_set.list1_iter()
+        .map(|(index, entry)| (index, entry.chr_ins().unwrap().field_ins_handle.instance_id, entry.unk0))
+        .collect::<Vec<_>>();
+    assert_eq!(list1, vec![(4, 7, 0), (1499, 8, 0x10)]);
+
+    let list2 = open_field_chr_set.list2_iter()
+        .map(|(index, _)| index)
+        .collect::<Vec<_>>();
+    assert_eq!(list2, vec![2]);
+
+    let characters = world_chr_man.characters()
+        .map(|entry| (entry.chr_ins.field_ins_handle.instance_id, entry.origin))
+        .collect::<Vec<_>>();
+    assert_eq!(characters, vec![(7, ChrSetOrigin::OpenField), (8, ChrSetOrigin::OpenField)]);
+}
+
+#[test]
+fn test_fieldinsselector_decoding() {
+    let selector = FieldInsSelector(0x10500012);
+    assert_eq!(selector.field_ins_type(), 1);
+    assert_eq!(selector.container(), 5);
+    assert_eq!(selector.index(), 0x12);
+    assert_eq!(FieldInsSelector::new(1, 5, 0x12), selector);
+
+    let selector = FieldInsSelector(0xffffffff);
+    assert_eq!(selector.field_ins_type(), 0xf);
+    assert_eq!(selector.container(), 0xff);
+    assert_eq!(selector.index(), 0xfffff);
+
+    // Parts that are too large don't bleed into the others.
+    assert_eq!(FieldInsSelector::new(0x12, 0, 0x100001), FieldInsSelector(0x20000001));
+}
+
+#[test]
+fn test_fieldinshandle_format_and_parse() {
+    const DATA: AlignedBuffer<8> = AlignedBuffer([0x10, 0x27, 0x00, 0x00, 0x12, 0x00, 0x50, 0x10]);
+
+    let handle = FieldInsHandle::read_from(DATA.0.as_slice()).unwrap();
+    assert_eq!(handle.instance_id, 10000);
+    assert_eq!(handle.selector.container(), 5);
+    assert_eq!(handle.to_string(), "10500012_10000");
+    assert_eq!("10500012_10000".parse::<FieldInsHandle>(), Ok(handle));
+
+    assert_eq!("10500012".parse::<FieldInsHandle>(), Err(FieldInsHandleParseError::MissingSeparator));
+    assert!(matches!(
+        "1050001g_10000".parse::<FieldInsHandle>(),
+        Err(FieldInsHandleParseError::InvalidSelector(_)),
+    ));
+    assert!(matches!(
+        "10500012_-1".parse::<FieldInsHandle>(),
+        Err(FieldInsHandleParseError::InvalidInstanceId(_)),
+    ));
+}
+
+#[test]
+fn test_fieldinshandle_ordering_and_hashing() {
+    let first = FieldInsHandle::new(1, FieldInsSelector::new(1, 2, 3));
+    let second = FieldInsHandle::new(1, FieldInsSelector::new(1, 2, 4));
+    let third = FieldInsHandle::new(2, FieldInsSelector::new(1, 0, 0));
+
+    let mut handles = vec![third, second, first];
+    handles.sort();
+    assert_eq!(handles, vec![first, second, third]);
+
+    let unique = [first, second, first, third]
+        .into_iter()
+        .collect::<std::collections::HashSet<_>>();
+    assert_eq!(unique.len(), 3);
+}
+
+#[test]
+fn test_worldchrman_map_id_for() {
+    let world_chr_man = fixture::world_chr_man();
+    world_chr_man.world_block_chr[5].map_id = LIMGRAVE;
+
+    let handle = FieldInsHandle::new(10000, FieldInsSelector::new(1, 5, 0x12));
+    assert_eq!(world_chr_man.map_id_for(handle), Some(LIMGRAVE));
+
+    let handle = FieldInsHandle::new(10000, FieldInsSelector::new(1, 200, 0x12));
+    assert_eq!(world_chr_man.map_id_for(handle), None);
+}
+
+#[test]
+fn test_mapid_conversions() {
+    const DATA: AlignedBuffer<4> = AlignedBuffer([0x00, 0x24, 0x2a, 0x3c]);
+
+    let map_id = MapId::read_from(DATA.0.as_slice()).unwrap();
+    assert_eq!(map_id, LIMGRAVE);
+    assert_eq!(u32::from(map_id), 0x3c2a2400);
+    assert_eq!(MapId::from(0x3c2a2400), map_id);
+    assert_eq!(map_id.to_string(), "m60_42_36_00");
+    assert_eq!(MapId::NONE.to_string(), "none");
+    assert!(LIMGRAVE < MapId::NONE);
+    assert!(STORMVEIL < LIMGRAVE);
+}
+
+#[test]
+fn test_mapid_parsing() {
+    assert_eq!("m60_42_36_00".parse(), Ok(LIMGRAVE));
+    assert_eq!("m10_00_00_00".parse(), Ok(STORMVEIL));
+    assert_eq!("m10".parse(), Ok(STORMVEIL));
+    assert_eq!("none".parse(), Ok(MapId::NONE));
+
+    assert_eq!("60_42_36_00".parse::<MapId>(), Err(MapIdParseError::InvalidFormat));
+    assert_eq!("m60_42_36_00_01".parse::<MapId>(), Err(MapIdParseError::InvalidFormat));
+    assert_eq!("m60__36_00".parse::<MapId>(), Err(MapIdParseError::InvalidFormat));
+    assert!(matches!("m60_42_360_00".parse::<MapId>(), Err(MapIdParseError::InvalidNumber(_))));
+}
+
+#[test]
+fn test_mapid_kinds_and_neighbours() {
+    assert!(LIMGRAVE.is_open_world());
+    assert!(MapId::new(61, 44, 44, 0).is_open_world());
+    assert!(STORMVEIL.is_legacy_dungeon());
+    assert!(!MapId::NONE.is_open_world() && !MapId::NONE.is_legacy_dungeon());
+
+    let neighbours = LIMGRAVE.neighbours()
+        .iter()
+        .map(MapId::to_string)
+        .collect::<Vec<_>>();
+    assert_eq!(neighbours, vec![
+        "m60_41_37_00", "m60_42_37_00", "m60_43_37_00",
+        "m60_41_36_00", "m60_43_36_00",
+        "m60_41_35_00", "m60_42_35_00", "m60_43_35_00",
+    ]);
+
+    // Scale is kept and tiles beyond the edge of the grid are left out.
+    let neighbours = MapId::new(60, 0, 10, 1).neighbours();
+    assert_eq!(neighbours.len(), 5);
+    assert!(neighbours.iter().all(|neighbour| neighbour.index == 1 && neighbour.block <= 1));
+
+    assert!(STORMVEIL.neighbours().is_empty());
+}
+
+#[test]
+fn test_chrins_layout() {
+    use std::mem::offset_of;
+
+    assert_eq!(offset_of!(ChrIns, field_ins_handle), 0x8);
+    assert_eq!(offset_of!(ChrIns, map_id_1), 0x30);
+    assert_eq!(offset_of!(ChrIns, chr_ctrl), 0x58);
+    assert_eq!(offset_of!(ChrIns, module_container), 0x190);
+    assert_eq!(offset_of!(ChrInsModuleContainer, physics), 0x68);
+    assert_eq!(offset_of!(ChrPhysicsModule, position), 0x70);
+}
+
+#[test]
+fn test_chrins_position() {
+    let position = Vector4 { x: -120.5, y: 32.25, z: 845.0, w: 1.0 };
+    let chr_ins = unsafe { &*fixture::chr_ins(1, MapId::NONE, position) };
+    assert_eq!(chr_ins.position(), Some(position));
+}
+
+#[test]
+fn test_chrctrl() {
+    use std::mem::offset_of;
+
+    assert_eq!(offset_of!(ChrCtrl, owner), 0x10);
+    assert_eq!(offset_of!(ChrCtrl, manipulator), 0x18);
+    assert_eq!(offset_of!(ChrCtrl, ragdoll_ins), 0x28);
+    assert_eq!(offset_of!(ChrCtrl, chr_collision), 0x30);
+
+    let mut data = AlignedBuffer([0u8; 0x130]);
+    data.0[0x128] = 2;
+
+    let chr_ctrl = unsafe { &mut *data.0.as_mut_ptr().cast::<ChrCtrl>() };
+    assert!(chr_ctrl.manipulator.is_null());
+    assert_eq!(chr_ctrl.ragdoll_state(), RagdollState::Ragdoll);
+    assert!(chr_ctrl.owner().is_none());
+
+    chr_ctrl.toggle_ragdoll();
+    assert_eq!(chr_ctrl.ragdoll_state(), RagdollState::Animated);
+    chr_ctrl.toggle_ragdoll();
+    assert_eq!(chr_ctrl.ragdoll_state(), RagdollState::Ragdoll);
+
+    chr_ctrl.set_ragdoll_state(RagdollState::Unknown(5));
+    assert_eq!(chr_ctrl.ragdoll_state(), RagdollState::Unknown(5));
+}
+
+#[test]
+fn test_ragdollstate_conversions() {
+    assert_eq!(RagdollState::from(0), RagdollState::Animated);
+    assert_eq!(RagdollState::from(2), RagdollState::Ragdoll);
+    assert_eq!(RagdollState::from(1), RagdollState::Unknown(1));
+
+    for value in 0..=u8::MAX {
+        assert_eq!(u8::from(RagdollState::from(value)), value);
+    }
+}
+
+#[test]
+fn test_session_state_conversions() {
+    assert_eq!(WorldState::try_from(3), Ok(WorldState::Host));
+    assert_eq!(WorldState::try_from(7), Ok(WorldState::ClosingLobby));
+    assert_eq!(WorldState::try_from(8), Err(8));
+    assert_eq!(ProtocolState::try_from(6), Ok(ProtocolState::InWorld));
+    assert_eq!(ProtocolState::try_from(0x1337), Err(0x1337));
+
+    for value in 0..8 {
+        assert_eq!(WorldState::try_from(value).map(|state| state as u32), Ok(value));
+        assert_eq!(ProtocolState::try_from(value).map(|state| state as u32), Ok(value));
+    }
+}
+
+#[test]
+fn test_cssessionmanager_states() {
+    const DATA: AlignedBuffer<0x118> = {
+        let mut data = [0; 0x118];
+        data[0xc] = 0x06;
+        data[0x10] = 0x09;
+        AlignedBuffer(data)
+    };
+
+    let session_manager: &CSSessionManager = unsafe { &*(DATA.0.as_ptr() as *const CSSessionManager) };
+    assert!(session_manager.players().unwrap().is_empty());
+    assert_eq!(session_manager.world_state(), Ok(WorldState::Client));
+    assert_eq!(session_manager.protocol_state(), Err(9));
+
+    let state = SessionState::of(session_manager);
+    assert_eq!(state.to_string(), "Client / Unknown(9)");
+}
+
+#[test]
+fn test_session_state_tracker() {
+    let offline = SessionState { world_state: Ok(WorldState::Offline), protocol_state: Ok(ProtocolState::Inactive) };
+    let host = SessionState { world_state: Ok(WorldState::Host), protocol_state: Ok(ProtocolState::Inactive) };
+    let unknown = SessionState { world_state: Err(12), protocol_state: Ok(ProtocolState::InWorld) };
+
+    let start = Instant::now();
+    let mut tracker = SessionStateTracker::new(2);
+    assert_eq!(tracker.current(), None);
+
+    let first = tracker.observe(offline, start).unwrap();
+    assert_eq!(first.from, None);
+    assert_eq!(first.to, offline);
+    assert!(tracker.observe(offline, start + Duration::from_secs(1)).is_none());
+
+    let second = tracker.observe(host, start + Duration::from_secs(2)).unwrap();
+    assert_eq!(second.from, Some(offline));
+    assert_eq!(second.at, start + Duration::from_secs(2));
+
+    // Oldest transition makes room once the history is full.
+    tracker.observe(unknown, start + Duration::from_secs(3));
+    let history = tracker.history().map(|transition| transition.to).collect::<Vec<_>>();
+    assert_eq!(history, vec![host, unknown]);
+    assert_eq!(tracker.current(), Some(unknown));
+
+    tracker.clear();
+    assert_eq!(tracker.current(), None);
+    assert_eq!(tracker.history().count(), 0);
+}
+
+#[test]
+fn test_cssessionmanager_players() {
+    use std::mem::offset_of;
+
+    assert_eq!(offset_of!(CSSessionManager, players), 0xd0);
+    assert_eq!(offset_of!(CSSessionManager, local_player), 0xf0);
+    assert_eq!(std::mem::size_of::<CSSessionManagerPlayerEntry>(), 0x28);
+
+    let session_manager = fixture::session_manager(6, 6, &[(0x1100001, 0), (0x1100002, 1), (0x1100003, -1)]);
+    let players = session_manager.players().unwrap();
+    assert_eq!(players.len(), 3);
+    assert_eq!(players[0].steam_id, 0x1100001);
+
+    let observed = ObservedPlayer::collect(session_manager).unwrap();
+    assert_eq!(observed[1], ObservedPlayer { steam_id: 0x1100002, session_slot: 1 });
+    assert_eq!(observed[2].session_slot, -1);
+
+    let start = SystemTime::UNIX_EPOCH;
+    let mut roster = SessionRoster::new();
+    assert_eq!(roster.observe(session_manager, start).len(), 3);
+    assert!(!roster.is_unreadable());
+
+    // Anything that doesn't look like a player list is ignored.
+    let garbage = fixture::session_manager(6, 6, &[(1, 0); 17]);
+    assert!(garbage.players().is_none());
+    assert!(roster.observe(garbage, start).is_empty());
+    assert!(roster.is_unreadable());
+    assert_eq!(roster.present().count(), 3);
+
+    let mut data = AlignedBuffer([0u8; 0x118]);
+    // last before first
+    data.0[0xd8..0xe0].copy_from_slice(&0x2000_usize.to_le_bytes());
+    data.0[0xe0..0xe8].copy_from_slice(&0x1000_usize.to_le_bytes());
+    data.0[0xe8..0xf0].copy_from_slice(&0x2000_usize.to_le_bytes());
+    let misread = unsafe { &*data.0.as_ptr().cast::<CSSessionManager>() };
+    assert!(misread.players().is_none());
+    assert_eq!(misread.players.len(), 0);
+}
+
+#[test]
+fn test_session_roster() {
+    let player = |steam_id, session_slot| ObservedPlayer { steam_id, session_slot };
+    let start = SystemTime::UNIX_EPOCH + Duration::from_secs(1_700_000_000);
+    let mut roster = SessionRoster::new();
+
+    let changes = roster.update(&[player(10, 0), player(20, 1)], start);
+    assert_eq!(changes, vec![RosterChange::Joined(10), RosterChange::Joined(20)]);
+    assert!(roster.update(&[player(10, 0), player(20, 1)], start + Duration::from_secs(1)).is_empty());
+
+    // Slots follow the session manager.
+    roster.update(&[player(10, 3), player(20, 1)], start + Duration::from_secs(2));
+    assert_eq!(roster.players()[0].session_slot, 3);
+
+    let changes = roster.update(&[player(10, 0)], start + Duration::from_secs(3));
+    assert_eq!(changes, vec![RosterChange::Left(20)]);
+    assert_eq!(roster.players()[1].left_at, Some(start + Duration::from_secs(3)));
+    assert_eq!(roster.present().map(|player| player.steam_id).collect::<Vec<_>>(), vec![10]);
+
+    // Rejoining starts a new stay.
+    let changes = roster.update(&[player(10, 0), player(20, 2)], start + Duration::from_secs(4));
+    assert_eq!(changes, vec![RosterChange::Joined(20)]);
+    assert_eq!(roster.players().len(), 3);
+    assert_eq!(roster.players()[2].joined_at, start + Duration::from_secs(4));
+    assert_eq!(roster.players()[2].session_slot, 2);
+
+    roster.clear();
+    assert!(roster.players().is_empty());
+}
+
+fn session_state(world_state: u32, protocol_state: u32) -> SessionState {
+    SessionState {
+        world_state: WorldState::try_from(world_state),
+        protocol_state: ProtocolState::try_from(protocol_state),
+    }
+}
+
+fn session_event(timestamp: u64, kind: SessionEventKind) -> SessionEvent {
+    SessionEvent { timestamp, kind, roster: vec![] }
+}
+
+#[test]
+fn test_session_event_kinds() {
+    let transition = |from: Option<SessionState>, to| SessionTransition { at: Instant::now(), from, to };
+
+    // Starting out idle isn't an event.
+    assert!(SessionEventKind::from_transition(&transition(None, session_state(0, 0))).is_empty());
+    assert_eq!(
+        SessionEventKind::from_transition(&transition(Some(session_state(0, 0)), session_state(1, 0))),
+        vec![SessionEventKind::LobbyCreated],
+    );
+    assert_eq!(
+        SessionEventKind::from_transition(&transition(Some(session_state(4, 0)), session_state(6, 1))),
+        vec![SessionEventKind::EnteredClientWorld, SessionEventKind::AwaitingWorldData],
+    );
+    assert_eq!(
+        SessionEventKind::from_transition(&transition(Some(session_state(6, 1)), session_state(6, 6))),
+        vec![SessionEventKind::EnteredWorld],
+    );
+    assert_eq!(
+        SessionEventKind::from_transition(&transition(Some(session_state(3, 6)), session_state(12, 2))),
+        vec![
+            SessionEventKind::WorldStateChanged { world_state: 12 },
+            SessionEventKind::ProtocolStateChanged { protocol_state: 2 },
+        ],
+    );
+
+    assert_eq!(SessionEventKind::from(RosterChange::Left(7)), SessionEventKind::PlayerLeft { steam_id: 7 });
+}
+
+#[test]
+fn test_session_event_json() {
+    let mut roster = SessionRoster::new();
+    roster.update(&[ObservedPlayer { steam_id: 42, session_slot: 1 }], SystemTime::now());
+
+    let at = SystemTime::UNIX_EPOCH + Duration::from_millis(1_700_000_000_123);
+    let event = SessionEvent::new(SessionEventKind::PlayerJoined { steam_id: 42 }, &roster, at);
+    let json = serde_json::to_string(&event).unwrap();
+    assert_eq!(
+        json,
+        r#"{"timestamp":1700000000123,"event":"PlayerJoined","steam_id":42,"roster":[{"steam_id":42,"session_slot":1}]}"#,
+    );
+
+    let parsed = parse_session_events(format!("{json}\n\n{json}\n").as_bytes()).unwrap();
+    assert_eq!(parsed, vec![event.clone(), event]);
+
+    let error = parse_session_events(format!("{json}\nnot json\n").as_bytes()).unwrap_err();
+    assert!(matches!(error, SessionReplayError::Parse { line: 2, .. }));
+}
+
+#[test]
+fn test_session_event_recorder_rotation() {
+    let directory = std::env::temp_dir().join(format!("session_events_{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&directory);
+    std::fs::create_dir_all(&directory).unwrap();
+
+    let line_size = serde_json::to_vec(&session_event(0, SessionEventKind::LobbyCreated)).unwrap().len() + 1;
+    let mut recorder = SessionEventRecorder::new(&directory, "events", (line_size * 2) as u64, 2);
+    for timestamp in 0..7 {
+        recorder.record(session_event(timestamp, SessionEventKind::LobbyCreated)).unwrap();
+    }
+
+    let timestamps = |index| {
+        read_session_events(recorder.rotated_path(index)).unwrap()
+            .iter()
+            .map(|event| event.timestamp)
+            .collect::<Vec<_>>()
+    };
+
+    assert_eq!(timestamps(0), vec![6]);
+    assert_eq!(timestamps(1), vec![4, 5]);
+    assert_eq!(timestamps(2), vec![2, 3]);
+    assert!(!recorder.rotated_path(3).exists());
+    assert_eq!(recorder.recent().count(), 7);
+
+    let roster = SessionRoster::new();
+    recorder.record_changes(None, &[RosterChange::Joined(1), RosterChange::Left(2)], &roster, SystemTime::now()).unwrap();
+    assert_eq!(
+        recorder.recent().rev().take(2).map(|event| event.kind).collect::<Vec<_>>(),
+        vec![SessionEventKind::PlayerLeft { steam_id: 2 }, SessionEventKind::PlayerJoined { steam_id: 1 }],
+    );
+
+    std::fs::remove_dir_all(&directory).unwrap();
+}
+
+#[test]
+fn test_session_replay_diff() {
+    use SessionEventKind::*;
+
+    let events = [
+        EnteredWorld,
+        JoinRequested, EnteredClientWorld, AwaitingWorldData, EnteredWorld, WentOffline,
+        JoinRequested, EnteredClientWorld, EnteredWorld, PlayerJoined { steam_id: 3 }, WentOffline,
+    ].into_iter()
+        .enumerate()
+        .map(|(timestamp, kind)| session_event(timestamp as u64, kind))
+        .collect::<Vec<_>>();
+
+    let sessions = split_sessions(&events);
+    assert_eq!(sessions.iter().map(|session| session.len()).collect::<Vec<_>>(), vec![1, 5, 5]);
+    assert!(split_sessions(&[]).is_empty());
+
+    assert_eq!(
+        diff_sessions(sessions[1], sessions[2]),
+        vec![
+            SessionEventDiff::Both(JoinRequested),
+            SessionEventDiff::Both(EnteredClientWorld),
+            SessionEventDiff::Left(AwaitingWorldData),
+            SessionEventDiff::Both(EnteredWorld),
+            SessionEventDiff::Right(PlayerJoined { steam_id: 3 }),
+            SessionEventDiff::Both(WentOffline),
+        ],
+    );
+}
+
+#[test]
+fn test_session_log_comparison() {
+    use SessionEventKind::*;
+
+    let directory = std::env::temp_dir().join(format!("session_comparison_{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&directory);
+    std::fs::create_dir_all(&directory).unwrap();
+
+    let write_log = |name: &str, kinds: &[SessionEventKind]| {
+        let path = directory.join(name);
+        let mut file = std::fs::File::create(&path).unwrap();
+        for (timestamp, kind) in kinds.iter().enumerate() {
+            serde_json::to_writer(&mut file, &session_event(timestamp as u64, *kind)).unwrap();
+            std::io::Write::write_all(&mut file, b"\n").unwrap();
+        }
+        path.to_string_lossy().into_owned()
+    };
+
+    let mut comparison = SessionLogComparison::default();
+    comparison.left_path = write_log("left.jsonl", &[JoinRequested, EnteredWorld, LobbyCreated, LobbyOpened]);
+    comparison.right_path = write_log("right.jsonl", &[JoinRequested, AwaitingWorldData, EnteredWorld]);
+    assert!(comparison.diff().is_none());
+
+    comparison.load().unwrap();
+    assert_eq!(comparison.left_sessions().len(), 2);
+    assert_eq!(
+        comparison.diff().unwrap(),
+        vec![
+            SessionEventDiff::Both(JoinRequested),
+            SessionEventDiff::Right(AwaitingWorldData),
+            SessionEventDiff::Both(EnteredWorld),
+        ],
+    );
+
+    comparison.left_session = 1;
+    assert_eq!(comparison.diff().unwrap()[0], SessionEventDiff::Left(LobbyCreated));
+    comparison.right_session = 1;
+    assert!(comparison.diff().is_none());
+
+    // A log that can't be read leaves the loaded ones alone.
+    comparison.right_path = directory.join("missing.jsonl").to_string_lossy().into_owned();
+    assert!(matches!(comparison.load(), Err(SessionReplayError::Io(_))));
+    assert_eq!(comparison.left_session, 1);
+    assert_eq!(comparison.right_sessions().len(), 1);
+
+    std::fs::remove_dir_all(&directory).unwrap();
+}
+
+#[test]
+fn test_worldareatimeclock_fields() {
+    let mut clock = WorldAreaTimeClock::from_raw(0);
+    clock.set_year(2022);
+    clock.set_milliseconds(999);
+    clock.set_month(2);
+    clock.set_day_of_week(5);
+    clock.set_day(25);
+    clock.set_hours(23);
+    clock.set_minutes(59);
+    clock.set_seconds(58);
+
+    assert_eq!(
+        clock.as_raw(),
+        2022 | 999 << 12 | 2 << 22 | 5 << 26 | 25 << 29 | 23 << 34 | 59 << 39 | 58 << 45,
+    );
+    assert_eq!(clock.time_of_day().unwrap().to_string(), "23:59:58.999");
+
+    // Values too wide for their field don't spill into the neighbours.
+    clock.set_hours(0xff);
+    assert_eq!(clock.hours(), 0x1f);
+    assert_eq!(clock.day(), 25);
+    assert_eq!(clock.minutes(), 59);
+
+    // Those still fit the field but aren't a time of day.
+    assert_eq!(clock.time_of_day(), Err(TimeOfDayError::Hours(0x1f)));
+    clock.set_hours(12);
+    clock.set_seconds(63);
+    assert_eq!(clock.time_of_day(), Err(TimeOfDayError::Seconds(63)));
+}
+
+#[test]
+fn test_worldareatimeclock_roundtrip() {
+    use rand::{Rng, SeedableRng};
+
+    type Field = (fn(&WorldAreaTimeClock) -> u64, fn(&mut WorldAreaTimeClock, u64), u32);
+    const FIELDS: [Field; 8] = [
+        (WorldAreaTimeClock::year, WorldAreaTimeClock::set_year, 12),
+        (WorldAreaTimeClock::milliseconds, WorldAreaTimeClock::set_milliseconds, 10),
+        (WorldAreaTimeClock::month, WorldAreaTimeClock::set_month, 4),
+        (WorldAreaTimeClock::day_of_week, WorldAreaTimeClock::set_day_of_week, 3),
+        (WorldAreaTimeClock::day, WorldAreaTimeClock::set_day, 5),
+        (WorldAreaTimeClock::hours, WorldAreaTimeClock::set_hours, 5),
+        (WorldAreaTimeClock::minutes, WorldAreaTimeClock::set_minutes, 6),
+        (WorldAreaTimeClock::seconds, WorldAreaTimeClock::set_seconds, 6),
+    ];
+
+    let mut rng = rand::rngs::StdRng::seed_from_u64(0x5eed);
+    for _ in 0..10_000 {
+        let original = WorldAreaTimeClock::from_raw(rng.gen());
+
+        // Unpacking and packing every field yields the same clock.
+        let mut repacked = WorldAreaTimeClock::from_raw(original.as_raw());
+        for (get, set, _) in FIELDS {
+            set(&mut repacked, get(&original));
+        }
+        assert_eq!(repacked, original);
+
+        // Packing a value only changes that field.
+        let (get, set, width) = FIELDS[rng.gen_range(0..FIELDS.len())];
+        let value = rng.gen_range(0..1u64 << width);
+        let mut clock = original;
+        set(&mut clock, value);
+        assert_eq!(get(&clock), value);
+        for (other_get, _, _) in FIELDS.iter().filter(|(other_get, _, _)| *other_get as usize != get as usize) {
+            assert_eq!(other_get(&clock), other_get(&original));
+        }
+
+        // Bits above the last field are left alone.
+        assert_eq!(clock.as_raw() >> 51, original.as_raw() >> 51);
+
+        let time_of_day = TimeOfDay::from_seconds_from_midnight(rng.gen());
+        let mut clock = original;
+        clock.set_time_of_day(time_of_day);
+        assert_eq!(clock.time_of_day(), Ok(time_of_day));
+        assert_eq!(clock.day(), original.day());
+    }
+}
+
+#[test]
+fn test_time_of_day() {
+    assert_eq!(TimeOfDay::from_hms(24, 0, 0), Err(TimeOfDayError::Hours(24)));
+    assert_eq!(TimeOfDay::from_hms(12, 60, 0), Err(TimeOfDayError::Minutes(60)));
+    assert_eq!(TimeOfDay::from_hms(12, 0, 60), Err(TimeOfDayError::Seconds(60)));
+    assert_eq!(TimeOfDay::from_hms_milli(12, 0, 0, 1000), Err(TimeOfDayError::Milliseconds(1000)));
+
+    let time_of_day = TimeOfDay::from_hms_milli(7, 5, 3, 20).unwrap();
+    assert_eq!(time_of_day.to_string(), "07:05:03.020");
+    assert_eq!(time_of_day.seconds_from_midnight(), 7 * 3600 + 5 * 60 + 3);
+    assert_eq!(TimeOfDay::from_seconds_from_midnight(24 * 3600 + 61), TimeOfDay::from_hms(0, 1, 1).unwrap());
+    assert!(TimeOfDay::from_hms(6, 0, 0).unwrap() < TimeOfDay::from_hms(18, 0, 0).unwrap());
+
+    let mut world_area_time = WorldAreaTime::new_zeroed();
+    world_area_time.clock.set_day(3);
+    world_area_time.clock.set_milliseconds(500);
+    world_area_time.set_time_of_day(18, 30, 15).unwrap();
+    assert_eq!(world_area_time.time_of_day().unwrap().to_string(), "18:30:15.000");
+    assert_eq!(world_area_time.clock.day(), 3);
+
+    assert_eq!(world_area_time.set_time_of_day(25, 0, 0), Err(TimeOfDayError::Hours(25)));
+    assert_eq!(world_area_time.time_of_day().unwrap().hours(), 18);
+}
+
+fn assert_close(actual: f32, expected: f32) {
+    assert!((actual - expected).abs() < 1e-3, "{actual} != {expected}");
+}
+
+fn assert_matrix_close(actual: Matrix4X4, expected: Matrix4X4) {
+    for (actual, expected) in actual.rows().iter().flatten().zip(expected.rows().iter().flatten()) {
+        assert_close(*actual, *expected);
+    }
+}
+
+fn cs_cam(view_matrix: Matrix4X4, fov_degrees: f32, aspect_ratio: f32) -> CSCam {
+    let mut cam = CSCam::new_zeroed();
+    cam.view_matrix = view_matrix;
+    cam.fov = fov_degrees.to_radians();
+    cam.aspect_ratio = aspect_ratio;
+    cam.near_plane = 0.1;
+    cam.far_plane = 1000.0;
+    cam
+}
+
+#[test]
+fn test_matrix_multiply() {
+    let shear = Matrix4X4::from_rows([
+        [1.0, 2.0, 0.0, 0.0],
+        [0.0, 1.0, 0.0, 0.0],
+        [0.0, 0.0, 1.0, 0.0],
+        [0.0, 0.0, 0.0, 1.0],
+    ]);
+    let translation = Matrix4X4::translation(Vector4::new(3.0, 4.0, 5.0));
+
+    assert_eq!(shear * Matrix4X4::IDENTITY, shear);
+    assert_eq!((shear * translation).rows()[3], [3.0, 4.0, 5.0, 1.0]);
+    assert_eq!((translation * shear).rows()[3], [3.0, 10.0, 5.0, 1.0]);
+    assert_eq!(shear.transpose().rows()[1], [2.0, 1.0, 0.0, 0.0]);
+
+    let point = translation.transform_point(Vector4::new(1.0, 1.0, 1.0));
+    assert_eq!(point, Vector4 { x: 4.0, y: 5.0, z: 6.0, w: 1.0 });
+    // Directions aren't affected by translation.
+    assert_eq!(translation.transform(Vector4::new(1.0, 1.0, 1.0)), Vector4::new(1.0, 1.0, 1.0));
+}
+
+#[test]
+fn test_matrix_inverse() {
+    let translation = Matrix4X4::translation(Vector4::new(3.0, 4.0, 5.0));
+    assert_matrix_close(translation.inverse().unwrap(), Matrix4X4::translation(Vector4::new(-3.0, -4.0, -5.0)));
+
+    let scale = Matrix4X4::from_rows([
+        [2.0, 0.0, 0.0, 0.0],
+        [0.0, 4.0, 0.0, 0.0],
+        [0.0, 0.0, 8.0, 0.0],
+        [0.0, 0.0, 0.0, 1.0],
+    ]);
+    assert_close(scale.determinant(), 64.0);
+    assert_eq!(scale.inverse().unwrap().rows()[2], [0.0, 0.0, 0.125, 0.0]);
+
+    // Rotation of 90 degrees around y, its inverse is its transpose.
+    let rotation = Matrix4X4::from_rows([
+        [0.0, 0.0, -1.0, 0.0],
+        [0.0, 1.0, 0.0, 0.0],
+        [1.0, 0.0, 0.0, 0.0],
+        [0.0, 0.0, 0.0, 1.0],
+    ]);
+    assert_matrix_close(rotation.inverse().unwrap(), rotation.transpose());
+
+    let transform = scale * rotation * translation;
+    assert_matrix_close(transform * transform.inverse().unwrap(), Matrix4X4::IDENTITY);
+
+    let singular = Matrix4X4::from_rows([
+        [1.0, 2.0, 3.0, 0.0],
+        [2.0, 4.0, 6.0, 0.0],
+        [0.0, 0.0, 1.0, 0.0],
+        [0.0, 0.0, 0.0, 1.0],
+    ]);
+    assert_eq!(singular.inverse(), None);
+}
+
+#[test]
+fn test_vector_math() {
+    let x = Vector4::new(1.0, 0.0, 0.0);
+    let y = Vector4::new(0.0, 1.0, 0.0);
+
+    assert_eq!(x.cross(&y), Vector4::new(0.0, 0.0, 1.0));
+    assert_eq!(x.dot(&y), 0.0);
+    assert_eq!((x + y) * 2.0, Vector4::new(2.0, 2.0, 0.0));
+    assert_eq!(-(x - y), Vector4::new(-1.0, 1.0, 0.0));
+    assert_eq!(Vector4::new(3.0, 4.0, 0.0).length(), 5.0);
+    assert_eq!(Vector4::new(3.0, 4.0, 0.0).normalize(), Vector4::new(0.6, 0.8, 0.0));
+    assert_eq!(Vector4::default().normalize(), Vector4::default());
+    assert_eq!(Vector4::new(1.0, 2.0, 2.0).distance(&Vector4::default()), 3.0);
+}
+
+#[test]
+fn test_cscam_projection() {
+    let cam = cs_cam(Matrix4X4::IDENTITY, 90.0, 16.0 / 9.0);
+    let screen = Vector2::new(1920.0, 1080.0);
+
+    assert_eq!(cam.position(), Vector4 { x: 0.0, y: 0.0, z: 0.0, w: 1.0 });
+    assert_eq!(cam.forward(), Vector4::new(0.0, 0.0, 1.0));
+
+    // Depth runs from 0 at the near plane to 1 at the far plane.
+    let projection = cam.projection_matrix();
+    let near = projection.transform_point(Vector4::new(0.0, 0.0, 0.1));
+    let far = projection.transform_point(Vector4::new(0.0, 0.0, 1000.0));
+    assert_close(near.z / near.w, 0.0);
+    assert_close(far.z / far.w, 1.0);
+
+    // A 90 degree fov sees as far up as it sees ahead.
+    let center = cam.world_to_screen(Vector4::new(0.0, 0.0, 10.0), screen).unwrap();
+    let top = cam.world_to_screen(Vector4::new(0.0, 10.0, 10.0), screen).unwrap();
+    let right = cam.world_to_screen(Vector4::new(10.0 * 16.0 / 9.0, 0.0, 10.0), screen).unwrap();
+    let outside = cam.world_to_screen(Vector4::new(-40.0, 0.0, 10.0), screen).unwrap();
+    assert_eq!((center.x, center.y), (960.0, 540.0));
+    assert_close(top.y, 0.0);
+    assert_close(right.x, 1920.0);
+    assert!(outside.x < 0.0);
+
+    assert_eq!(cam.world_to_screen(Vector4::new(0.0, 0.0, -10.0), screen), None);
+}
+
+#[test]
+fn test_cscam_world_to_screen_moved() {
+    // Standing at (5, 2, 0) looking down the x axis.
+    let view_matrix = Matrix4X4::from_rows([
+        [0.0, 0.0, -1.0, 0.0],
+        [0.0, 1.0, 0.0, 0.0],
+        [1.0, 0.0, 0.0, 0.0],
+        [5.0, 2.0, 0.0, 1.0],
+    ]);
+    let cam = cs_cam(view_matrix, 90.0, 1.0);
+    let screen = Vector2::new(100.0, 100.0);
+
+    assert_eq!(cam.position(), Vector4 { x: 5.0, y: 2.0, z: 0.0, w: 1.0 });
+    assert_eq!(cam.forward(), Vector4::new(1.0, 0.0, 0.0));
+    assert_eq!(cam.right(), Vector4::new(0.0, 0.0, -1.0));
+    assert_eq!(cam.up(), Vector4::new(0.0, 1.0, 0.0));
+
+    let center = cam.world_to_screen(Vector4::new(15.0, 2.0, 0.0), screen).unwrap();
+    assert_close(center.x, 50.0);
+    assert_close(center.y, 50.0);
+
+    let right = cam.world_to_screen(Vector4::new(15.0, 2.0, -10.0), screen).unwrap();
+    assert_close(right.x, 100.0);
+    assert_close(right.y, 50.0);
+
+    let below = cam.world_to_screen(Vector4::new(15.0, -3.0, 0.0), screen).unwrap();
+    assert_close(below.y, 75.0);
+
+    assert_eq!(cam.world_to_screen(Vector4::new(0.0, 2.0, 0.0), screen), None);
+
+    // A camera without a valid transform can't project anything.
+    let cam = cs_cam(Matrix4X4::default(), 90.0, 1.0);
+    assert_eq!(cam.world_to_screen(Vector4::new(0.0, 0.0, 10.0), screen), None);
+}
+
+#[test]
+fn test_free_camera() {
+    use std::f32::consts::FRAC_PI_2;
+
+    let view_matrix = Matrix4X4::from_rows([
+        [0.0, 0.0, -1.0, 0.0],
+        [0.0, 1.0, 0.0, 0.0],
+        [1.0, 0.0, 0.0, 0.0],
+        [5.0, 2.0, 0.0, 1.0],
+    ]);
+    let mut cam = cs_cam(view_matrix, 90.0, 1.0);
+    let mut camera_update = [0xcc_u8; 20];
+    let mut free_camera = unsafe { FreeCamera::with_update_target(camera_update.as_mut_ptr()) };
+
+    // Nothing happens until the camera is detached.
+    free_camera.update(&mut cam, &FreeCameraInput { forward: 1.0, ..Default::default() }, 1.0);
+    assert_eq!(cam.view_matrix, view_matrix);
+
+    free_camera.enable(&mut cam);
+    assert!(free_camera.is_active());
+    assert!(free_camera.patch_error().is_none());
+    assert_eq!(camera_update[..16], [0x90; 16]);
+    assert_eq!(camera_update[16..], [0xcc; 4]);
+    assert_close(free_camera.yaw, FRAC_PI_2);
+    assert_close(free_camera.pitch, 0.0);
+    assert_matrix_close(free_camera.view_matrix(), view_matrix);
+
+    free_camera.update(&mut cam, &FreeCameraInput { forward: 1.0, right: 1.0, ..Default::default() }, 0.5);
+    let diagonal = 5.0 / 2.0_f32.sqrt();
+    assert_close(cam.position().x, 5.0 + diagonal);
+    assert_close(cam.position().z, -diagonal);
+
+    free_camera.update(&mut cam, &FreeCameraInput { up: 1.0, fast: true, ..Default::default() }, 0.1);
+    assert_close(cam.position().y, 7.0);
+
+    // Looking straight up would flip the camera over.
+    free_camera.update(&mut cam, &FreeCameraInput { pitch: 10.0, fov: 10.0, ..Default::default() }, 0.0);
+    assert!(free_camera.pitch < FRAC_PI_2);
+    assert_eq!(cam.fov, 3.0);
+    assert!(cam.forward().y > 0.99);
+
+    free_camera.disable(&mut cam);
+    assert!(!free_camera.is_active());
+    assert_eq!(camera_update, [0xcc; 20]);
+    assert_eq!(cam.view_matrix, view_matrix);
+    assert_close(cam.fov, 90.0_f32.to_radians());
+
+    let mut free_camera = FreeCamera::new();
+    assert!(!free_camera.has_update_patch());
+    assert!(matches!(free_camera.update_patch_error(), Some(CameraUpdateError::NotConfigured)));
+    free_camera.enable(&mut cam);
+    free_camera.update(&mut cam, &FreeCameraInput { forward: 1.0, ..Default::default() }, 1.0);
+    assert_close(cam.position().x, 15.0);
+}
+
+#[test]
+fn test_spectate_cycling() {
+    let world_chr_man = populated_world_chr_man();
+    let dbg = fixture::world_chr_man_dbg();
+    let mut spectate = Spectate::new(SpectateKeys::default());
+    let spectating = |dbg: &WorldChrManDbg| dbg.cam_override()
+        .map(|chr_ins| chr_ins.field_ins_handle.instance_id);
+
+    // Cycling does nothing until spectating.
+    spectate.handle(SpectateAction::Next, world_chr_man, dbg);
+    assert_eq!(spectating(dbg), None);
+
+    spectate.handle(SpectateAction::Toggle, world_chr_man, dbg);
+    assert!(spectate.is_active());
+    assert_eq!(spectating(dbg), Some(1));
+    assert_eq!(spectate.target(), Some(fixture::chr_handle(1)));
+
+    let mut order = vec![];
+    for _ in 0..4 {
+        spectate.handle(SpectateAction::Next, world_chr_man, dbg);
+        order.push(spectating(dbg).unwrap());
+    }
+    assert_eq!(order, vec![2, 4, 3, 1]);
+
+    spectate.handle(SpectateAction::Previous, world_chr_man, dbg);
+    assert_eq!(spectating(dbg), Some(3));
+
+    // Characters that can't be seen by the query are skipped.
+    spectate.query = ChrQuery::new().map_id(LIMGRAVE);
+    spectate.handle(SpectateAction::Next, world_chr_man, dbg);
+    assert_eq!(spectating(dbg), Some(1));
+
+    spectate.handle(SpectateAction::ToggleRagdoll, world_chr_man, dbg);
+    let player = world_chr_man.main_player().unwrap();
+    assert_eq!(player.chr_ctrl().unwrap().ragdoll_state(), RagdollState::Ragdoll);
+
+    spectate.handle(SpectateAction::Toggle, world_chr_man, dbg);
+    assert!(!spectate.is_active());
+    assert_eq!(spectate.target(), None);
+    assert_eq!(spectating(dbg), None);
+}
+
+#[test]
+fn test_spectate_hotkeys() {
+    let keys = toml::from_str::<SpectateKeys>("toggle = \"Ctrl+Home\"\nnext = \"F2\"").unwrap();
+    assert_eq!(keys.toggle.to_string(), "Ctrl+Home");
+    assert_eq!(keys.next.to_string(), "F2");
+    // Keys that aren't configured keep their default.
+    assert_eq!(keys.previous, SpectateKeys::default().previous);
+
+    let spectate = Spectate::new(keys);
+    let mut keyboard = KeyboardState::default();
+    keyboard.update(|key| key == 0x24 || key == 0x71);
+    assert_eq!(spectate.actions(&keyboard), vec![SpectateAction::Next]);
+
+    keyboard.update(|_| false);
+    keyboard.update(|key| key == KEY_CONTROL || key == 0x24);
+    assert_eq!(spectate.actions(&keyboard), vec![SpectateAction::Toggle]);
+
+    keyboard.set_captured(true);
+    keyboard.update(|_| false);
+    keyboard.update(|key| key == 0x71);
+    assert!(spectate.actions(&keyboard).is_empty());
+}
+
+#[test]
+fn test_free_camera_keys() {
+    let keys = toml::from_str::<FreeCameraKeys>("forward = \"Up\"\nfast = \"Alt\"").unwrap();
+    assert_eq!(keys.forward.key, 0x26);
+    assert_eq!(keys.back, FreeCameraKeys::default().back);
+
+    let mut keyboard = KeyboardState::default();
+    keyboard.update(|key| key == 0x26 || key == KEY_ALT);
+    assert!(keys.forward.is_down(&keyboard));
+    assert!(keys.fast.is_down(&keyboard));
+    assert!(!keys.toggle.is_pressed(&keyboard));
+
+    assert!(toml::from_str::<FreeCameraKeys>("look = \"Ctrl+\"").is_err());
+}
+
+#[test]
+fn test_spectate_restoration() {
+    let world_chr_man = fixture::world_chr_man();
+    let first = fixture::chr_ins(1, LIMGRAVE, Vector4::default());
+    let second = fixture::chr_ins(2, LIMGRAVE, Vector4::default());
+    let third = fixture::chr_ins(3, LIMGRAVE, Vector4::default());
+    fixture::fill_chr_set(&mut world_chr_man.chr_set_1, &[first, second, third]);
+
+    let dbg = fixture::world_chr_man_dbg();
+    dbg.cam_override_chr_ins = third;
+
+    let mut spectate = Spectate::new(SpectateKeys::default());
+    spectate.enable(world_chr_man, dbg);
+    spectate.cycle(world_chr_man, dbg, 1);
+    assert_eq!(spectate.target(), Some(fixture::chr_handle(2)));
+
+    // Moves on once the target despawns.
+    fixture::fill_chr_set(&mut world_chr_man.chr_set_1, &[first, std::ptr::null_mut(), third]);
+    spectate.update(world_chr_man, dbg);
+    assert_eq!(spectate.target(), Some(fixture::chr_handle(1)));
+    assert_eq!(dbg.cam_override_chr_ins, first);
+
+    // The override from before spectating is put back.
+    spectate.disable(world_chr_man, dbg);
+    assert_eq!(dbg.cam_override_chr_ins, third);
+
+    // Unless it has despawned.
+    spectate.enable(world_chr_man, dbg);
+    fixture::fill_chr_set(&mut world_chr_man.chr_set_1, &[]);
+    spectate.update(world_chr_man, dbg);
+    assert_eq!(spectate.target(), None);
+    spectate.disable(world_chr_man, dbg);
+    assert!(dbg.cam_override().is_none());
+}
+
+#[test]
+fn test_world_chr_man_dbg_layout() {
+    use std::mem::offset_of;
+
+    assert_eq!(offset_of!(WorldChrManDbg, vftable), 0x0);
+    assert_eq!(offset_of!(WorldChrManDbg, manipulator), 0xa8);
+    assert_eq!(offset_of!(WorldChrManDbg, player_session_holder), 0xb0);
+    assert_eq!(offset_of!(WorldChrManDbg, cam_override_chr_ins), 0xb8);
+
+    assert_eq!(offset_of!(ChrDbgFlags, unkf), 0xf);
+    assert_eq!(std::mem::size_of::<ChrDbgFlags>(), ChrDbgFlags::COUNT);
+}
+
+#[test]
+fn test_chr_dbg_flags() {
+    let mut data = [0u8; 0x11];
+    data[0x4] = 1;
+    data[0x8] = 1;
+    data[0x10] = 0xcc;
+
+    let flags = unsafe { ChrDbgFlagsRef::new(NonNull::new(data.as_mut_ptr().cast()).unwrap()) }.unwrap();
+    assert_eq!(flags.get(0x4), Some(true));
+    assert_eq!(flags.get(0x0), Some(false));
+    assert_eq!(flags.get(0x10), None);
+    assert_eq!(flags.read().enabled().collect::<Vec<_>>(), vec![0x4, 0x8]);
+
+    flags.set(0x0, true);
+    flags.set(0x8, false);
+    flags.set(0x10, true);
+    assert_eq!(flags.read().unk0, 1);
+    assert_eq!(flags.read().enabled().collect::<Vec<_>>(), vec![0x0, 0x4]);
+
+    flags.clear();
+    assert_eq!(flags.read(), ChrDbgFlags::default());
+    // Nothing past the block is touched.
+    assert_eq!(data[0x10], 0xcc);
+
+    // Anything but 0s and 1s isn't a block of flags.
+    let mut data = [0u8; 0x10];
+    data[0x3] = 0x40;
+    let result = unsafe { ChrDbgFlagsRef::new(NonNull::new(data.as_mut_ptr().cast()).unwrap()) };
+    assert!(matches!(result, Err(ChrDbgFlagsError::NotFlags(flags)) if flags.unk3 == 0x40));
+}
+
+#[test]
+fn test_config_parsing() {
+    let config = r#"
+        log_level = "debug"
+        chr_dbg_flags = 0x3d5df38
+        dictionary = "names/resource_names.txt.gz"
+
+        [window]
+        width = 1024.0
+
+        [panels]
+        flver_repository = false
+
+        [export]
+        directory = "exports"
+        format = "json"
+        characters = "chr"
+
+        [hotkeys.spectate]
+        toggle = "Ctrl+Home"
+
+        [hotkeys.free_camera]
+        look = "MouseMiddle"
+
+        [features]
+        free_camera = false
+
+        [camera_update]
+        rva = 0x1a2b3c
+        original = [0x0f, 0x29, 0x41, 0x10, 0x0f, 0x29, 0x49, 0x20, 0x0f, 0x29, 0x51, 0x30, 0x0f, 0x29, 0x59, 0x40]
+    "#.parse::<Config>().unwrap();
+
+    assert_eq!(config.log_level, LogLevel::Debug);
+    assert_eq!(config.window.width, 1024.0);
+    assert_eq!(config.window.height, 600.0);
+    assert!(!config.panels.flver_repository);
+    assert!(config.panels.world_chr_man);
+    assert_eq!(config.export.path(&config.export.characters), std::path::Path::new("exports").join("chr.jsonl"));
+    assert_eq!(config.export.singletons, "singletons");
+    assert_eq!(config.hotkeys.spectate.toggle.to_string(), "Ctrl+Home");
+    assert_eq!(config.hotkeys.spectate.next, SpectateKeys::default().next);
+    assert_eq!(config.hotkeys.free_camera.look.key, 0x04);
+    assert!(!config.features.free_camera);
+    assert!(config.features.spectate);
+    let camera_update = config.camera_update.as_ref().unwrap();
+    assert_eq!(camera_update.rva, 0x1a2b3c);
+    assert_eq!(camera_update.original.len(), 16);
+    assert_eq!(Config::default().camera_update, None::<CameraUpdateConfig>);
+    assert_eq!(config.chr_dbg_flags, Some(0x3d5df38));
+    assert_eq!(config.dictionary, std::path::Path::new("names/resource_names.txt.gz"));
+    assert_eq!(Config::default().chr_dbg_flags, None);
+
+    // An empty file is the default config, and the default config survives
+    // being written out.
+    assert_eq!("".parse::<Config>().unwrap(), Config::default());
+    let written = toml::to_string(&Config::default()).unwrap();
+    assert_eq!(written.parse::<Config>().unwrap(), Config::default());
+}
+
+#[test]
+fn test_config_errors() {
+    let error = "[panels]\nflver_repositroy = false".parse::<Config>().unwrap_err();
+    assert!(error.to_string().contains("flver_repositroy"), "{error}");
+
+    let error = "[hotkeys.spectate]\nnext = \"Ctrl+Nope\"".parse::<Config>().unwrap_err();
+    assert!(error.to_string().contains("unknown key \"Nope\""), "{error}");
+
+    let error = "log_level = \"loud\"".parse::<Config>().unwrap_err();
+    assert!(matches!(error, ConfigError::Parse(_)));
+}
+
+#[test]
+fn test_config_watcher() {
+    let directory = std::env::temp_dir().join(format!("config_watcher_{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&directory);
+    std::fs::create_dir_all(&directory).unwrap();
+
+    let path = directory.join(crate::config::CONFIG_FILE_NAME);
+    let start = Instant::now();
+    let mut watcher = ConfigWatcher::new(&path);
+
+    // Missing files load as the defaults once.
+    assert_eq!(watcher.reload_if_changed(start), Some(Config::default()));
+    assert!(watcher.error().is_none());
+    assert_eq!(watcher.reload_if_changed(start + Duration::from_secs(2)), None);
+
+    let write = |contents: &str, modified: u64| {
+        std::fs::write(&path, contents).unwrap();
+        std::fs::File::options()
+            .write(true)
+            .open(&path)
+            .unwrap()
+            .set_modified(std::time::UNIX_EPOCH + Duration::from_secs(modified))
+            .unwrap();
+    };
+
+    write("log_level = \"warn\"", 1000);
+    // Checks are spaced out.
+    assert_eq!(watcher.reload_if_changed(start + Duration::from_millis(2500)), None);
+    let config = watcher.reload_if_changed(start + Duration::from_secs(3)).unwrap();
+    assert_eq!(config.log_level, LogLevel::Warn);
+    assert_eq!(watcher.reload_if_changed(start + Duration::from_secs(4)), None);
+
+    // Broken files keep the error around until fixed.
+    write("log_level = ", 2000);
+    assert_eq!(watcher.reload_if_changed(start + Duration::from_secs(5)), None);
+    assert!(matches!(watcher.error(), Some(ConfigError::Parse(_))));
+    assert_eq!(watcher.reload_if_changed(start + Duration::from_secs(6)), None);
+    assert!(watcher.error().is_some());
+
+    write("log_level = \"trace\"", 3000);
+    let config = watcher.reload_if_changed(start + Duration::from_secs(7)).unwrap();
+    assert_eq!(config.log_level, LogLevel::Trace);
+    assert!(watcher.error().is_none());
+
+    // Reloading by hand doesn't care about the file having changed.
+    assert_eq!(watcher.reload().unwrap().log_level, LogLevel::Trace);
+
+    std::fs::remove_dir_all(&directory).unwrap();
+}
+
+#[test]
+fn test_export_write_records() {
+    #[derive(serde::Serialize)]
+    struct Record {
+        name: &'static str,
+        hash: u32,
+        left_at: Option<u64>,
+    }
+
+    let records = [
+        Record { name: "c0000", hash: 0x1234, left_at: None },
+        Record { name: "say \"hi\"", hash: 0x5678, left_at: Some(10) },
+    ];
+
+    let mut csv = vec![];
+    write_records(&mut csv, &records, ExportFormat::Csv, true).unwrap();
+    assert_eq!(
+        String::from_utf8(csv).unwrap(),
+        "name, hash, left_at\n\"c0000\", 4660, \n\"say \"\"hi\"\"\", 22136, 10\n",
+    );
+
+    let mut csv = vec![];
+    write_records(&mut csv, &records[..1], ExportFormat::Csv, false).unwrap();
+    assert_eq!(String::from_utf8(csv).unwrap(), "\"c0000\", 4660, \n");
+
+    let mut json = vec![];
+    write_records(&mut json, &records, ExportFormat::Json, true).unwrap();
+    assert_eq!(
+        String::from_utf8(json).unwrap(),
+        "{\"name\":\"c0000\",\"hash\":4660,\"left_at\":null}\n{\"name\":\"say \\\"hi\\\"\",\"hash\":22136,\"left_at\":10}\n",
+    );
 }
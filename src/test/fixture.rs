@@ -0,0 +1,561 @@
+//! Builders for synthetic game structures. Everything allocated in here is
+//! leaked on purpose so that the pointers handed out stay valid for the
+//! remainder of the test run.
+
+use std::alloc;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::ffi;
+use std::mem;
+use std::ptr;
+
+use zerocopy::{FromBytes, FromZeroes};
+
+use crate::game::cs::{
+    CSFile, CSFileRepository, CSFileRepositoryMutex, CSSessionManager, CSSessionManagerPlayerEntry, ChrCtrl, ChrIns, ChrInsModuleContainer, ChrPhysicsModule, ChrSet,
+    ChrSetEntry, FieldInsHandle, FieldInsSelector, MapId, WorldChrMan, WorldChrManDbg,
+};
+use crate::game::dl::{
+    DLAllocator, DLAllocatorRef, DLAllocatorVMT, DLFileOperatorVMT, DLPlainLightMutex, DLRuntimeClass, DLWString,
+    GameAllocator,
+};
+use crate::game::fd4::FD4BasicHashString;
+use crate::game::matrix::Vector4;
+use crate::game::fd4::{hash_resource_name, FD4ResCap, FD4ResCapHolder};
+use crate::game::stl::{
+    StdList, StdListNode, StdPair, StdTree, StdTreeNode, StdUnorderedMap, StdVector,
+};
+
+/// Allocates a zeroed T and leaks it. Used for nodes that we need to wire up
+/// before (or without ever) writing a value.
+pub fn alloc_zeroed<T>() -> *mut T {
+    let layout = alloc::Layout::new::<T>();
+    let ptr = unsafe { alloc::alloc_zeroed(layout) as *mut T };
+    assert!(!ptr.is_null(), "Could not allocate fixture");
+    ptr
+}
+
+/// Builds a balanced MSVC tree from values that are already sorted.
+pub fn std_tree<T>(values: Vec<T>) -> StdTree<T> {
+    let head = alloc_zeroed::<StdTreeNode<T>>();
+    let nodes = values.into_iter()
+        .map(|value| {
+            let node = alloc_zeroed::<StdTreeNode<T>>();
+            unsafe { ptr::addr_of_mut!((*node).value).write(value) };
+            node
+        })
+        .collect::<Vec<_>>();
+
+    unsafe {
+        (*head).is_nil = 1;
+        (*head).color = 1;
+        (*head).parent = link_subtree(&nodes, head, head);
+        (*head).left = nodes.first().copied().unwrap_or(head);
+        (*head).right = nodes.last().copied().unwrap_or(head);
+    }
+
+    StdTree {
+        allocator: DLAllocatorRef::default(),
+        head,
+        size: nodes.len(),
+    }
+}
+
+unsafe fn link_subtree<T>(
+    nodes: &[*mut StdTreeNode<T>],
+    parent: *mut StdTreeNode<T>,
+    head: *mut StdTreeNode<T>,
+) -> *mut StdTreeNode<T> {
+    if nodes.is_empty() {
+        return head;
+    }
+
+    let middle = nodes.len() / 2;
+    let node = nodes[middle];
+    (*node).color = 1;
+    (*node).parent = parent;
+    (*node).left = link_subtree(&nodes[..middle], node, head);
+    (*node).right = link_subtree(&nodes[middle + 1..], node, head);
+    node
+}
+
+/// Builds an MSVC tree for a std::map. Entries must be sorted by key.
+pub fn std_map<K, V>(entries: Vec<(K, V)>) -> StdTree<StdPair<K, V>> {
+    std_tree(
+        entries.into_iter()
+            .map(|(first, second)| StdPair { first, second })
+            .collect()
+    )
+}
+
+pub fn std_list<T>(values: Vec<T>) -> StdList<T> {
+    let head = alloc_zeroed::<StdListNode<T>>();
+    let size = values.len();
+
+    let mut previous = head;
+    for value in values.into_iter() {
+        let node = alloc_zeroed::<StdListNode<T>>();
+        unsafe {
+            ptr::addr_of_mut!((*node).value).write(value);
+            (*node).prev = previous;
+            (*previous).next = node;
+        }
+        previous = node;
+    }
+
+    unsafe {
+        (*previous).next = head;
+        (*head).prev = previous;
+    }
+
+    StdList {
+        allocator: DLAllocatorRef::default(),
+        head,
+        size,
+    }
+}
+
+pub fn std_vector<T>(values: Vec<T>) -> StdVector<T> {
+    let mut values = mem::ManuallyDrop::new(values);
+    let first = values.as_mut_ptr();
+
+    StdVector {
+        allocator: DLAllocatorRef::default(),
+        first,
+        last: first.wrapping_add(values.len()),
+        end: first.wrapping_add(values.capacity()),
+    }
+}
+
+/// Builds an MSVC unordered_map. Only the element list is populated in a
+/// meaningful way, every bucket is left pointing at the list's head.
+pub fn std_unordered_map<K, V>(
+    entries: Vec<(K, V)>,
+    bucket_count: usize,
+) -> StdUnorderedMap<K, V> {
+    let list = std_list(
+        entries.into_iter()
+            .map(|(first, second)| StdPair { first, second })
+            .collect()
+    );
+    let buckets = std_vector(vec![list.head; bucket_count * 2]);
+
+    // Zeroed first as the padding isn't visible from here. None of the fields
+    // implement Drop so overwriting them is fine.
+    let mut map: StdUnorderedMap<K, V> = unsafe { mem::zeroed() };
+    map.max_load_factor = 1.0;
+    map.list = list;
+    map.buckets = buckets;
+    map.mask = bucket_count.saturating_sub(1);
+    map.max_index = bucket_count;
+    map
+}
+
+/// Builds a DLWString. Short strings get inlined, longer ones are put on the
+/// heap like the game would.
+pub fn dl_wstring(string: &str) -> DLWString {
+    let mut units = string.encode_utf16().collect::<Vec<u16>>();
+    let length = units.len();
+
+    let mut bytes = [0u8; 32];
+    let capacity = if length < 8 {
+        for (i, unit) in units.iter().enumerate() {
+            bytes[i * 2..i * 2 + 2].copy_from_slice(&unit.to_le_bytes());
+        }
+        7
+    } else {
+        units.push(0);
+        let units = mem::ManuallyDrop::new(units);
+        bytes[0..8].copy_from_slice(&(units.as_ptr() as usize).to_le_bytes());
+        units.capacity() - 1
+    };
+
+    bytes[16..24].copy_from_slice(&length.to_le_bytes());
+    bytes[24..32].copy_from_slice(&capacity.to_le_bytes());
+
+    DLWString::read_from(bytes.as_slice()).unwrap()
+}
+
+/// Builds a ResCap with a hashed name that isn't linked into any holder yet.
+pub fn fd4_res_cap<'a>(name: &str) -> *mut FD4ResCap<'a, ()> {
+    let res_cap = alloc_zeroed::<FD4ResCap<()>>();
+    unsafe {
+        let header = &mut (*res_cap).header;
+        ptr::addr_of_mut!(header.name.string).write(dl_wstring(name));
+        header.name.hash = hash_resource_name(name);
+        header.reference_count = 1;
+    }
+    res_cap
+}
+
+/// Builds a ResCap holder and inserts ResCaps for the names. Collisions are
+/// handled like the game does, by making the inserted ResCap the new head
+/// of the slot.
+pub fn fd4_res_cap_holder<'a>(capacity: u32, names: &[&str]) -> *mut FD4ResCapHolder<'a, ()> {
+    let slots = Box::leak(vec![ptr::null::<FD4ResCap<()>>(); capacity as usize].into_boxed_slice());
+
+    let holder = alloc_zeroed::<FD4ResCapHolder<()>>();
+    unsafe {
+        (*holder).capacity = capacity;
+        (*holder).map = slots.as_ptr();
+
+        for name in names {
+            let res_cap = fd4_res_cap(name);
+            let slot = &mut slots[(hash_resource_name(name) % capacity) as usize];
+
+            (*res_cap).header.owning_repository = &*holder;
+            (*res_cap).header.next_item = *slot;
+            *slot = res_cap;
+        }
+    }
+
+    holder
+}
+
+/// Builds a CSFileRepository with both holders filled. All of its mutexes
+/// are distinct.
+pub fn cs_file_repository<'a>(
+    holder1: &[&str],
+    holder2: &[&str],
+) -> &'a CSFileRepository<'a> {
+    let repository = alloc_zeroed::<CSFileRepository>();
+    unsafe {
+        ptr::copy_nonoverlapping(fd4_res_cap_holder(4, holder1), ptr::addr_of_mut!((*repository).holder1), 1);
+        ptr::copy_nonoverlapping(fd4_res_cap_holder(4, holder2), ptr::addr_of_mut!((*repository).holder2), 1);
+
+        for i in 0..5 {
+            let mutex = &*alloc_zeroed::<CSFileRepositoryMutex>();
+            ptr::addr_of_mut!((*repository).mutexes[i]).write(mutex);
+        }
+
+        &*repository
+    }
+}
+
+/// How often the mutex has been entered without being left again. Relies on
+/// the mutex backend used for tests.
+pub fn lock_depth(mutex: &DLPlainLightMutex) -> i32 {
+    // CRITICAL_SECTION::RecursionCount
+    let critical_section = unsafe { &*mutex.critical_section.get() };
+    i32::from_le_bytes(critical_section[0xc..0x10].try_into().unwrap())
+}
+
+/// Builds a character with its physics module set up so that it has a
+/// position.
+pub fn chr_ins<'a>(instance_id: u32, map_id: MapId, position: Vector4) -> *mut ChrIns<'a> {
+    let chr_ins = alloc_zeroed::<ChrIns>();
+    let module_container = alloc_zeroed::<ChrInsModuleContainer>();
+    let physics = alloc_zeroed::<ChrPhysicsModule>();
+
+    unsafe {
+        (*physics).owner = chr_ins;
+        (*physics).position = position;
+        (*module_container).physics = physics;
+
+        let chr_ctrl = alloc_zeroed::<ChrCtrl>();
+        (*chr_ctrl).owner = chr_ins as usize;
+        (*chr_ins).chr_ctrl = chr_ctrl as usize;
+        (*chr_ins).field_ins_handle = chr_handle(instance_id);
+        (*chr_ins).map_id_1 = map_id;
+        (*chr_ins).module_container = module_container;
+    }
+
+    chr_ins
+}
+
+/// Handle for a character in the first WorldBlockChr. Uses the instance id
+/// as the index too so handles are unique.
+pub fn chr_handle(instance_id: u32) -> FieldInsHandle {
+    FieldInsHandle::new(instance_id, FieldInsSelector::new(1, 0, instance_id))
+}
+
+/// Points a ChrSet at a new entry array holding the characters. Null
+/// characters leave an empty entry.
+pub fn fill_chr_set<'a>(chr_set: &mut ChrSet<'a>, characters: &[*mut ChrIns<'a>]) {
+    let entries = characters.iter()
+        .map(|chr_ins| {
+            let mut entry = ChrSetEntry::new_zeroed();
+            entry.chr_ins = *chr_ins;
+            entry
+        })
+        .collect::<Vec<_>>();
+
+    chr_set.capacity = entries.len() as i32;
+    chr_set.count = entries.len() as i32;
+    chr_set.entries = Box::leak(entries.into_boxed_slice()).as_ptr();
+}
+
+/// Builds an empty WorldChrMan without a main player.
+pub fn world_chr_man<'a>() -> &'a mut WorldChrMan<'a> {
+    unsafe { &mut *alloc_zeroed::<WorldChrMan>() }
+}
+
+pub fn world_chr_man_dbg<'a>() -> &'a mut WorldChrManDbg<'a> {
+    unsafe { &mut *alloc_zeroed::<WorldChrManDbg>() }
+}
+
+/// Builds a session manager in the given states with the remote players
+/// given as (steam id, session slot).
+pub fn session_manager(
+    world_state: u32,
+    protocol_state: u32,
+    players: &[(u64, i32)],
+) -> &'static CSSessionManager {
+    let players = players.iter()
+        .map(|(steam_id, session_slot)| {
+            let mut entry = CSSessionManagerPlayerEntry::new_zeroed();
+            entry.steam_id = *steam_id;
+            entry.session_slot = *session_slot;
+            entry
+        })
+        .collect();
+
+    let session_manager = alloc_zeroed::<CSSessionManager>();
+    unsafe {
+        (*session_manager).world_state = world_state;
+        (*session_manager).protocol_state = protocol_state;
+        ptr::addr_of_mut!((*session_manager).players).write(std_vector(players));
+        &*session_manager
+    }
+}
+
+/// Allocator backed by the Rust allocator that keeps track of what's live.
+#[derive(Default)]
+pub struct TestAllocator {
+    allocations: RefCell<HashMap<usize, alloc::Layout>>,
+}
+
+impl TestAllocator {
+    pub fn live_allocations(&self) -> usize {
+        self.allocations.borrow().len()
+    }
+
+    pub fn allocation_size(&self, ptr: *const u8) -> usize {
+        self.allocations.borrow()
+            .get(&(ptr as usize))
+            .map(|layout| layout.size())
+            .unwrap_or(0)
+    }
+}
+
+unsafe impl GameAllocator for TestAllocator {
+    fn allocate(&self, size: usize, alignment: usize) -> *mut u8 {
+        let layout = alloc::Layout::from_size_align(size, alignment).unwrap();
+        let ptr = unsafe { alloc::alloc(layout) };
+        self.allocations.borrow_mut().insert(ptr as usize, layout);
+        ptr
+    }
+
+    unsafe fn deallocate(&self, ptr: *mut u8) {
+        let layout = self.allocations.borrow_mut()
+            .remove(&(ptr as usize))
+            .expect("Freed memory that wasn't allocated by this allocator");
+
+        alloc::dealloc(ptr, layout);
+    }
+
+    fn as_raw(&self) -> usize {
+        self as *const _ as usize
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AllocatorCall {
+    Allocate(usize, usize),
+    Reallocate(usize, usize),
+    Deallocate,
+    /// The destructor only records the call, the mock itself is dropped from
+    /// Rust.
+    Destroy(bool),
+}
+
+/// Stands in for one of the game's allocators. It implements the allocator
+/// vftable and records every call that is made through it.
+#[repr(C)]
+pub struct MockDLAllocator {
+    pub base: DLAllocator,
+    pub calls: RefCell<Vec<AllocatorCall>>,
+    pub heap: TestAllocator,
+}
+
+impl MockDLAllocator {
+    pub const HEAP_CAPACITY: usize = 0x1000000;
+
+    pub fn new() -> Box<Self> {
+        Box::new(Self {
+            base: DLAllocator { vftable: mock_dl_allocator_vmt() },
+            calls: Default::default(),
+            heap: Default::default(),
+        })
+    }
+
+    /// Takes the calls recorded so far.
+    pub fn take_calls(&self) -> Vec<AllocatorCall> {
+        mem::take(&mut self.calls.borrow_mut())
+    }
+
+    fn from_base<'a>(base: *const DLAllocator) -> &'a Self {
+        unsafe { &*(base as *const Self) }
+    }
+}
+
+/// Builds the vftable for MockDLAllocator. Slot 2 is left empty as its
+/// purpose is unknown.
+pub fn mock_dl_allocator_vmt() -> DLAllocatorVMT {
+    let table: &'static [usize; 14] = Box::leak(Box::new([
+        mock_destructor as *const () as usize,
+        mock_allocator_id as *const () as usize,
+        0,
+        mock_heap_flags as *const () as usize,
+        mock_heap_capacity as *const () as usize,
+        mock_heap_size as *const () as usize,
+        mock_heap_capacity as *const () as usize,
+        mock_heap_allocation_count as *const () as usize,
+        mock_msize as *const () as usize,
+        mock_allocate as *const () as usize,
+        mock_allocate_aligned as *const () as usize,
+        mock_reallocate as *const () as usize,
+        mock_reallocate_aligned as *const () as usize,
+        mock_deallocate as *const () as usize,
+    ]));
+
+    DLAllocatorVMT::from_raw(table.as_ptr() as usize)
+}
+
+extern "C" fn mock_destructor(this: *const DLAllocator, free: bool) {
+    let mock = MockDLAllocator::from_base(this);
+    mock.calls.borrow_mut().push(AllocatorCall::Destroy(free));
+}
+
+extern "C" fn mock_allocator_id(_this: *const DLAllocator) -> u32 {
+    0x1337
+}
+
+extern "C" fn mock_heap_flags(_this: *const DLAllocator, flags: *mut u64) -> *const u64 {
+    flags
+}
+
+extern "C" fn mock_heap_capacity(_this: *const DLAllocator) -> usize {
+    MockDLAllocator::HEAP_CAPACITY
+}
+
+extern "C" fn mock_heap_size(this: *const DLAllocator) -> usize {
+    let heap = &MockDLAllocator::from_base(this).heap;
+    heap.allocations.borrow().values().map(|layout| layout.size()).sum()
+}
+
+extern "C" fn mock_heap_allocation_count(this: *const DLAllocator) -> usize {
+    MockDLAllocator::from_base(this).heap.live_allocations()
+}
+
+extern "C" fn mock_msize(this: *const DLAllocator, ptr: *const u8) -> usize {
+    MockDLAllocator::from_base(this).heap.allocation_size(ptr)
+}
+
+extern "C" fn mock_allocate(this: *const DLAllocator, size: usize) -> *mut u8 {
+    mock_allocate_aligned(this, size, 8)
+}
+
+extern "C" fn mock_allocate_aligned(this: *const DLAllocator, size: usize, alignment: usize) -> *mut u8 {
+    let mock = MockDLAllocator::from_base(this);
+    mock.calls.borrow_mut().push(AllocatorCall::Allocate(size, alignment));
+    mock.heap.allocate(size, alignment)
+}
+
+extern "C" fn mock_reallocate(this: *const DLAllocator, ptr: *mut u8, size: usize) -> *mut u8 {
+    mock_reallocate_aligned(this, ptr, size, 8)
+}
+
+extern "C" fn mock_reallocate_aligned(
+    this: *const DLAllocator,
+    ptr: *mut u8,
+    size: usize,
+    alignment: usize,
+) -> *mut u8 {
+    let mock = MockDLAllocator::from_base(this);
+    mock.calls.borrow_mut().push(AllocatorCall::Reallocate(size, alignment));
+
+    let old_size = mock.heap.allocation_size(ptr);
+    let new = mock.heap.allocate(size, alignment);
+    unsafe {
+        ptr::copy_nonoverlapping(ptr, new, old_size.min(size));
+        mock.heap.deallocate(ptr);
+    }
+    new
+}
+
+extern "C" fn mock_deallocate(this: *const DLAllocator, ptr: *mut u8) {
+    let mock = MockDLAllocator::from_base(this);
+    mock.calls.borrow_mut().push(AllocatorCall::Deallocate);
+    unsafe { mock.heap.deallocate(ptr) };
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FileOperatorCall {
+    GetRuntimeClass,
+    Destructor(u64),
+    GetRepository1Resource(u32),
+    Unk3(u32, usize, u32),
+}
+
+thread_local! {
+    static FILE_OPERATOR_CALLS: RefCell<Vec<(usize, FileOperatorCall)>> = Default::default();
+}
+
+/// Takes the calls made through mock_cs_file's vftable on this thread along
+/// with the this pointer that was passed.
+pub fn take_file_operator_calls() -> Vec<(usize, FileOperatorCall)> {
+    FILE_OPERATOR_CALLS.with(|calls| mem::take(&mut *calls.borrow_mut()))
+}
+
+/// Builds a CSFile whose vftable records every call made through it.
+pub fn mock_cs_file<'a>() -> &'a CSFile<'a> {
+    let table: &'static [usize; 4] = Box::leak(Box::new([
+        mock_get_runtime_class as *const () as usize,
+        mock_file_destructor as *const () as usize,
+        mock_get_repository_1_resource as *const () as usize,
+        mock_unk3 as *const () as usize,
+    ]));
+
+    let file = alloc_zeroed::<CSFile>();
+    unsafe {
+        ptr::addr_of_mut!((*file).vftable).write(DLFileOperatorVMT::from_raw(table.as_ptr() as usize));
+        ptr::addr_of_mut!((*file).file_repository_1).write(&*alloc_zeroed::<CSFileRepository>());
+        &*file
+    }
+}
+
+fn record_file_operator_call(this: *const CSFile, call: FileOperatorCall) {
+    FILE_OPERATOR_CALLS.with(|calls| calls.borrow_mut().push((this as usize, call)));
+}
+
+pub const MOCK_RUNTIME_CLASS: usize = 0x143a7d8d0;
+
+extern "C" fn mock_get_runtime_class(this: *const CSFile) -> *const DLRuntimeClass {
+    record_file_operator_call(this, FileOperatorCall::GetRuntimeClass);
+    MOCK_RUNTIME_CLASS as *const DLRuntimeClass
+}
+
+extern "C" fn mock_file_destructor(this: *const CSFile, flags: u64) -> *const ffi::c_void {
+    record_file_operator_call(this, FileOperatorCall::Destructor(flags));
+    this as *const ffi::c_void
+}
+
+extern "C" fn mock_get_repository_1_resource(
+    this: *const CSFile,
+    name: *const FD4BasicHashString,
+) -> *const ffi::c_void {
+    let hash = unsafe { (*name).hash };
+    record_file_operator_call(this, FileOperatorCall::GetRepository1Resource(hash));
+    name as *const ffi::c_void
+}
+
+extern "C" fn mock_unk3(
+    this: *const CSFile,
+    name: *const FD4BasicHashString,
+    res_cap: *const FD4ResCap<()>,
+    unk: u32,
+) {
+    let hash = unsafe { (*name).hash };
+    record_file_operator_call(this, FileOperatorCall::Unk3(hash, res_cap as usize, unk));
+}
@@ -1,3 +1,6 @@
 pub mod patch;
 pub mod singleton;
 pub mod debug_display;
+pub mod hash_dictionary;
+pub mod input;
+pub mod vtable;
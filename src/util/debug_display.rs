@@ -1,16 +1,33 @@
 use hudhook::imgui::{TreeNodeFlags, Ui};
 
-use crate::game::cs::{CSCam, CSSessionManager, ChrIns, ChrSet, OpenFieldChrSet, WorldChrMan};
+use crate::game::cs::{
+    CSCam, CSSessionManager, ChrDbgFlags, ChrDbgFlagsRef, ChrIns, ChrSet, OpenFieldChrSet, WorldChrMan, WorldChrManDbg,
+};
 use crate::game::fd4::FlverRepository;
+use crate::export::Export;
+use crate::free_camera::FreeCamera;
+use crate::spectate::Spectate;
+use crate::session::{
+    SessionEventDiff, SessionEventRecorder, SessionLogComparison, SessionRoster, SessionStateTracker,
+};
 use crate::util;
-use crate::game::{cs::CSCamera, world_area_time::WorldAreaTime};
+use crate::util::hash_dictionary::get_dictionary;
+use crate::game::{cs::CSCamera, world_area_time::{TimeOfDay, WorldAreaTime}};
 
-use super::singleton::DLRFLocatable;
+use super::singleton::{DLRFLocatable, LookupError};
+
+const ERROR_COLOR: [f32; 4] = [1.0, 0.4, 0.4, 1.0];
 
 pub trait DebugDisplay {
     fn render_debug(&self, ui: &&mut Ui);
 }
 
+/// Controls for changing the state of the game. Rendered below the
+/// DebugDisplay of the same type.
+pub trait DebugEdit {
+    fn render_edit(&mut self, ui: &&mut Ui);
+}
+
 impl DebugDisplay for CSCamera<'_> {
     fn render_debug(&self, ui: &&mut Ui) {
         if ui.collapsing_header("Pers cam 1", TreeNodeFlags::empty()) {
@@ -38,12 +55,111 @@ impl DebugDisplay for CSCam {
     fn render_debug(&self, ui: &&mut Ui) {
         ui.text(format!("unk8: {}", self.unk8));
         ui.text(format!("unkc: {}", self.unkc));
+
+        let position = self.position();
+        ui.text(format!("Position: {} {} {}", position.x, position.y, position.z));
+        let forward = self.forward();
+        ui.text(format!("Forward: {} {} {}", forward.x, forward.y, forward.z));
+        ui.text(format!("FOV: {}", self.fov.to_degrees()));
         ui.text(format!("Aspect ratio: {}", self.aspect_ratio));
         ui.text(format!("Far plane: {}", self.far_plane));
         ui.text(format!("Near plane: {}", self.near_plane));
     }
 }
 
+impl DebugDisplay for FreeCamera {
+    fn render_debug(&self, ui: &&mut Ui) {
+        ui.text(format!("Active: {}", self.is_active()));
+        if let Some(e) = self.update_patch_error() {
+            ui.text_colored(ERROR_COLOR, format!("No camera update patch, the game will fight over the camera: {e:?}"));
+        }
+        if let Some(e) = self.patch_error() {
+            ui.text_colored(ERROR_COLOR, format!("Could not patch the camera update: {e:?}"));
+        }
+
+        ui.text(format!("Position: {} {} {}", self.position.x, self.position.y, self.position.z));
+        ui.text("WASD to move, Q/E for down/up, Z/X to zoom, hold shift to go fast");
+        ui.text("Hold the right mouse button to look around");
+    }
+}
+
+impl DebugEdit for FreeCamera {
+    fn render_edit(&mut self, ui: &&mut Ui) {
+        ui.slider("Speed", 1.0, 200.0, &mut self.speed);
+
+        let mut fov = self.fov.to_degrees();
+        if ui.slider("FOV", 10.0, 150.0, &mut fov) {
+            self.fov = fov.to_radians();
+        }
+    }
+}
+
+impl DebugDisplay for Spectate {
+    fn render_debug(&self, ui: &&mut Ui) {
+        ui.text(format!("Active: {}", self.is_active()));
+
+        match self.target() {
+            Some(target) => ui.text(format!("Spectating: {target}")),
+            None => ui.text("Spectating: nobody"),
+        }
+
+        ui.text(format!(
+            "Keys: toggle {}, previous {}, next {}, ragdoll {}",
+            self.keys.toggle,
+            self.keys.previous,
+            self.keys.next,
+            self.keys.ragdoll,
+        ));
+    }
+}
+
+impl DebugEdit for Spectate {
+    fn render_edit(&mut self, ui: &&mut Ui) {
+        let mut max_distance = self.query.max_distance.unwrap_or(0.0);
+        if ui.slider("Max distance (0 for any)", 0.0, 500.0, &mut max_distance) {
+            self.query.max_distance = Some(max_distance).filter(|distance| *distance > 0.0);
+        }
+    }
+}
+
+impl DebugDisplay for WorldChrManDbg<'_> {
+    fn render_debug(&self, ui: &&mut Ui) {
+        match self.cam_override() {
+            Some(chr_ins) => ui.text(format!("Camera override: {}", chr_ins.field_ins_handle)),
+            None => ui.text("Camera override: none"),
+        }
+    }
+}
+
+impl DebugDisplay for ChrDbgFlagsRef {
+    fn render_debug(&self, ui: &&mut Ui) {
+        ui.text_disabled("Which flag does what hasn't been mapped, they're named after their offset.");
+
+        let enabled = self.read().enabled().map(|index| format!("unk{index:x}")).collect::<Vec<_>>();
+        if enabled.is_empty() {
+            ui.text("Debug flags: none");
+        } else {
+            ui.text(format!("Debug flags: {}", enabled.join(", ")));
+        }
+    }
+}
+
+impl DebugEdit for ChrDbgFlagsRef {
+    fn render_edit(&mut self, ui: &&mut Ui) {
+        let flags = self.read();
+        for index in 0..ChrDbgFlags::COUNT {
+            let mut enabled = flags.get(index).unwrap_or_default();
+            if ui.checkbox(format!("unk{index:x}"), &mut enabled) {
+                self.set(index, enabled);
+            }
+        }
+
+        if ui.button("Clear debug flags") {
+            self.clear();
+        }
+    }
+}
+
 impl DebugDisplay for WorldAreaTime {
     fn render_debug(&self, ui: &&mut Ui) {
         let year = self.clock.year();
@@ -66,13 +182,146 @@ impl DebugDisplay for WorldAreaTime {
 
         let seconds = self.clock.seconds();
         ui.text(format!("Seconds: {seconds}"));
+
+        let milliseconds = self.clock.milliseconds();
+        ui.text(format!("Milliseconds: {milliseconds}"));
+    }
+}
+
+impl DebugEdit for WorldAreaTime {
+    fn render_edit(&mut self, ui: &&mut Ui) {
+        let time_of_day = self.time_of_day().unwrap_or_else(|e| {
+            ui.text_colored(ERROR_COLOR, format!("Clock doesn't hold a valid time: {e:?}"));
+            TimeOfDay::default()
+        });
+        let mut hours = time_of_day.hours();
+        let mut minutes = time_of_day.minutes();
+        let mut seconds = time_of_day.seconds();
+
+        let changed = ui.slider("Set hours", 0, 23, &mut hours)
+            | ui.slider("Set minutes", 0, 59, &mut minutes)
+            | ui.slider("Set seconds", 0, 59, &mut seconds);
+
+        if changed {
+            if let Err(e) = self.set_time_of_day(hours, minutes, seconds) {
+                tracing::error!("Could not set time of day: {e:?}");
+            }
+        }
     }
 }
 
 impl DebugDisplay for CSSessionManager {
     fn render_debug(&self, ui: &&mut Ui) {
-        ui.text(format!("World state: {}", self.world_state));
-        ui.text(format!("Protocol state: {}", self.protocol_state));
+        match self.world_state() {
+            Ok(world_state) => ui.text(format!("World state: {world_state:?}")),
+            Err(raw) => ui.text(format!("World state: Unknown ({raw})")),
+        }
+
+        match self.protocol_state() {
+            Ok(protocol_state) => ui.text(format!("Protocol state: {protocol_state:?}")),
+            Err(raw) => ui.text(format!("Protocol state: Unknown ({raw})")),
+        }
+    }
+}
+
+impl DebugDisplay for SessionStateTracker {
+    fn render_debug(&self, ui: &&mut Ui) {
+        match self.current() {
+            Some(state) => ui.text(format!("Current: {state}")),
+            None => ui.text("Current: not observed yet"),
+        }
+
+        for transition in self.history().rev() {
+            let elapsed = transition.at.elapsed().as_secs_f32();
+            match transition.from {
+                Some(from) => ui.text(format!("{elapsed:.1}s ago: {from} -> {}", transition.to)),
+                None => ui.text(format!("{elapsed:.1}s ago: {}", transition.to)),
+            }
+        }
+    }
+}
+
+impl DebugDisplay for SessionRoster {
+    fn render_debug(&self, ui: &&mut Ui) {
+        if ui.button("Export roster") {
+            if let Err(e) = self.export() {
+                tracing::error!("Could not export session roster: {e:?}");
+            }
+        }
+
+        ui.text_disabled("The player list layout hasn't been checked against the game.");
+        if self.is_unreadable() {
+            ui.text_colored(ERROR_COLOR, "The session manager's player list doesn't look like one, not updating.");
+        }
+        ui.text(format!("In our world: {}", self.present().count()));
+
+        for player in self.players() {
+            let joined = player.joined_at.elapsed().unwrap_or_default().as_secs();
+
+            match player.left_at {
+                None => ui.text(format!(
+                    "[{}] {} joined {joined}s ago",
+                    player.session_slot,
+                    player.steam_id,
+                )),
+                Some(left_at) => ui.text_disabled(format!(
+                    "[{}] {} joined {joined}s ago, left {}s ago",
+                    player.session_slot,
+                    player.steam_id,
+                    left_at.elapsed().unwrap_or_default().as_secs(),
+                )),
+            }
+        }
+    }
+}
+
+impl DebugDisplay for SessionEventRecorder {
+    fn render_debug(&self, ui: &&mut Ui) {
+        ui.text(format!("Writing to {}", self.path().display()));
+
+        for event in self.recent().rev() {
+            ui.text(format!("{} {:?} ({} players)", event.timestamp, event.kind, event.roster.len()));
+        }
+    }
+}
+
+/// Lines only in the left log are red, lines only in the right one green.
+impl DebugEdit for SessionLogComparison {
+    fn render_edit(&mut self, ui: &&mut Ui) {
+        const LEFT_COLOR: [f32; 4] = [1.0, 0.4, 0.4, 1.0];
+        const RIGHT_COLOR: [f32; 4] = [0.4, 1.0, 0.4, 1.0];
+
+        ui.input_text("Left log", &mut self.left_path).build();
+        ui.input_text("Right log", &mut self.right_path).build();
+        if ui.button("Load logs") {
+            self.error = self.load().err();
+        }
+        if let Some(error) = &self.error {
+            ui.text_colored(LEFT_COLOR, format!("Could not load logs: {error:?}"));
+        }
+
+        let session_counts = [self.left_sessions().len(), self.right_sessions().len()];
+        let sessions = [&mut self.left_session, &mut self.right_session];
+        for ((label, count), session) in ["Left", "Right"].into_iter().zip(session_counts).zip(sessions) {
+            let mut index = *session as i32;
+            if ui.input_int(format!("{label} session of {count}"), &mut index).build() {
+                *session = index.clamp(0, count.saturating_sub(1) as i32) as usize;
+            }
+        }
+        ui.separator();
+
+        let Some(diff) = self.diff() else {
+            ui.text("Load two logs with sessions to compare them");
+            return;
+        };
+
+        for line in diff {
+            match line {
+                SessionEventDiff::Both(kind) => ui.text(format!("  {kind:?}")),
+                SessionEventDiff::Left(kind) => ui.text_colored(LEFT_COLOR, format!("< {kind:?}")),
+                SessionEventDiff::Right(kind) => ui.text_colored(RIGHT_COLOR, format!("> {kind:?}")),
+            }
+        }
     }
 }
 
@@ -88,6 +337,12 @@ impl DebugDisplay for WorldChrMan<'_> {
         ui.text(format!("World Grid Area Chr List Count: {world_grid_area_chr_list_count}"));
         ui.text(format!("World Area List Count: {world_area_list_count}"));
 
+        if ui.button("Export characters") {
+            if let Err(e) = self.export() {
+                tracing::error!("Could not export characters: {e:?}");
+            }
+        }
+
         if ui.collapsing_header("ChrSet 1", TreeNodeFlags::empty()) {
             self.chr_set_1.render_debug(ui);
         }
@@ -107,6 +362,20 @@ impl DebugDisplay for WorldChrMan<'_> {
         if ui.collapsing_header("OpenFieldChrSet", TreeNodeFlags::empty()) {
             self.open_field_chr_set.render_debug(ui);
         }
+
+        if ui.collapsing_header("World blocks", TreeNodeFlags::empty()) {
+            for (index, world_block_chr) in self.world_block_chr.iter().enumerate() {
+                let chr_set = &world_block_chr.chr_set1;
+                if chr_set.count <= 0 {
+                    continue;
+                }
+
+                let label = format!("[{index}] {} ({} characters)", world_block_chr.map_id, chr_set.count);
+                if ui.collapsing_header(label, TreeNodeFlags::empty()) {
+                    chr_set.render_debug(ui);
+                }
+            }
+        }
     }
 }
 
@@ -115,14 +384,20 @@ impl DebugDisplay for ChrSet<'_> {
         ui.text(format!("Character count: {}", self.count));
         ui.text(format!("Character capacity: {}", self.capacity));
 
-        // for element in self.character_iter() {
-        //     let chr_ins = unsafe {(element.chr_ins).as_ref().unwrap()};
-        //
-        //     let label = chr_ins.field_ins_handle.to_string();
-        //     if ui.collapsing_header(label, TreeNodeFlags::empty()) {
-        //         chr_ins.render_debug(ui);
-        //     }
-        // }
+        for element in self.character_iter() {
+            let label = format!(
+                "[{}] {} {} ({:x}, {:x})",
+                element.index,
+                element.chr_ins.field_ins_handle,
+                element.chr_ins.map_id_1,
+                element.unk8,
+                element.unkc,
+            );
+
+            if ui.collapsing_header(label, TreeNodeFlags::empty()) {
+                element.chr_ins.render_debug(ui);
+            }
+        }
     }
 }
 
@@ -132,35 +407,106 @@ impl DebugDisplay for ChrIns<'_> {
         ui.text(format!("Map ID origin 1: {}", self.map_id_origin_1));
         ui.text(format!("Map ID 2: {}", self.map_id_2));
         ui.text(format!("Map ID origin 2: {}", self.map_id_origin_2));
+        ui.text(format!("Open world: {}", self.map_id_1.is_open_world()));
+        if let Some(chr_ctrl) = self.chr_ctrl() {
+            ui.text(format!("Ragdoll state: {:?}", chr_ctrl.ragdoll_state()));
+        }
+
+        match self.physics_module() {
+            Some(physics) => {
+                let position = physics.position;
+                ui.text(format!("Position: {:.2}, {:.2}, {:.2}", position.x, position.y, position.z));
+            },
+            None => ui.text("No physics module"),
+        }
     }
 }
 
 
 impl DebugDisplay for OpenFieldChrSet<'_> {
     fn render_debug(&self, ui: &&mut Ui) {
-        self.base.render_debug(ui)
+        self.base.render_debug(ui);
+
+        if ui.collapsing_header("List 1", TreeNodeFlags::empty()) {
+            for (index, entry) in self.list1_iter() {
+                let Some(chr_ins) = entry.chr_ins() else {
+                    continue;
+                };
+
+                let label = format!("[{index}] {} ({:x})", chr_ins.field_ins_handle, entry.unk0);
+                if ui.collapsing_header(label, TreeNodeFlags::empty()) {
+                    chr_ins.render_debug(ui);
+                }
+            }
+        }
+
+        if ui.collapsing_header("List 2", TreeNodeFlags::empty()) {
+            for (index, entry) in self.list2_iter() {
+                ui.text(format!("[{index}] {:x} {:x} {:x}", entry.unk0, entry.unk8, entry.unkc));
+            }
+        }
     }
 }
 
 impl DebugDisplay for FlverRepository<'_> {
     fn render_debug(&self, ui: &&mut Ui) {
-        ui.text(format!("Capacity: {}", self.map.capacity));
+        let stats = self.map.stats();
+        ui.text(format!("Capacity: {}", stats.capacity));
+        ui.text(format!("Entries: {}", stats.entries));
+        ui.text(format!("Occupied slots: {}", stats.occupied_slots));
+        ui.text(format!("Longest chain: {}", stats.longest_chain));
+        ui.text(format!("Load factor: {:.2}", stats.load_factor()));
+
+        let dictionary = get_dictionary();
+        ui.text(format!("Dictionary names: {}", dictionary.len()));
+        ui.text(format!("Unknown hashes: {}", dictionary.unknown_hashes().len()));
+        let (matched, mismatched) = dictionary.hash_checks();
+        ui.text(format!("Game hashes checked: {matched} matched, {mismatched} mismatched"));
+
+        if ui.button("Export repository") {
+            if let Err(e) = self.export() {
+                tracing::error!("Could not export FlverRepository: {e:?}");
+            }
+        }
+        ui.same_line();
+        if ui.button("Export unknown hashes") {
+            if let Err(e) = dictionary.export() {
+                tracing::error!("Could not export unknown hashes: {e:?}");
+            }
+        }
+        ui.separator();
 
+        // Unlocked, the mutex guarding this holder hasn't been found yet so
+        // this can race the loader.
         for rescap in self.map.iter() {
-            ui.text(rescap.header.name.string.to_string());
+            ui.text(dictionary.display_name(&rescap.header.name));
         }
     }
 }
 
-pub fn render_debug_singleton<T: DLRFLocatable + DebugDisplay + 'static>(ui: &&mut Ui) {
-    let singleton = util::singleton::get_instance::<T>()
-        .expect(&format!("Could not get reflection data for {}", T::DLRF_NAME));
+pub fn render_edit_singleton<T: DLRFLocatable + DebugDisplay + DebugEdit + 'static>(ui: &&mut Ui) {
+    match util::singleton::get_instance::<T>() {
+        Ok(Some(instance)) => if ui.collapsing_header(T::DLRF_NAME, TreeNodeFlags::empty()) {
+            instance.render_debug(ui);
+            instance.render_edit(ui);
+            ui.separator();
+        },
+        Ok(None) => ui.text(format!("No instance of {} found", T::DLRF_NAME)),
+        Err(e) => render_lookup_error::<T>(ui, &e),
+    }
+}
 
-    match singleton {
-        Some(instance) => if ui.collapsing_header(T::DLRF_NAME, TreeNodeFlags::empty()) {
+pub fn render_debug_singleton<T: DLRFLocatable + DebugDisplay + 'static>(ui: &&mut Ui) {
+    match util::singleton::get_instance::<T>() {
+        Ok(Some(instance)) => if ui.collapsing_header(T::DLRF_NAME, TreeNodeFlags::empty()) {
             instance.render_debug(&ui);
             ui.separator();
         },
-        None => ui.text(format!("No instance of {} found", T::DLRF_NAME)),
+        Ok(None) => ui.text(format!("No instance of {} found", T::DLRF_NAME)),
+        Err(e) => render_lookup_error::<T>(ui, &e),
     }
 }
+
+fn render_lookup_error<T: DLRFLocatable>(ui: &&mut Ui, e: &LookupError) {
+    ui.text_colored(ERROR_COLOR, format!("Could not get reflection data for {}: {e:?}", T::DLRF_NAME));
+}
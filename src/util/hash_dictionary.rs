@@ -0,0 +1,242 @@
+use std::collections;
+use std::fs;
+use std::io;
+use std::io::BufRead;
+use std::path::Path;
+use std::sync;
+
+use flate2::read::GzDecoder;
+
+use crate::config;
+use crate::game::fd4::{hash_resource_name, FD4BasicHashString};
+
+static DICTIONARY: sync::OnceLock<HashDictionary> = sync::OnceLock::new();
+
+#[derive(Debug)]
+pub enum DictionaryError {
+    FileOpen(io::Error),
+    FileRead(io::Error),
+}
+
+/// Resolves resource name hashes back to their names. The game frees or
+/// never populates the string of some FD4BasicHashStrings, leaving us with
+/// only the hash. The dictionary is built from a list of known names which
+/// get hashed up front.
+///
+/// The list is a gzipped text file with one resource name per line. Empty
+/// lines and lines starting with a # are skipped.
+///
+/// The hashing has yet to be checked against the game. Names the game still
+/// has are used to check it as they come by, resolved names are marked as
+/// unverified until at least one hash matched and none didn't.
+#[derive(Default)]
+pub struct HashDictionary {
+    names: collections::HashMap<u32, String>,
+    collisions: usize,
+    // Hashes we've been asked about but couldn't resolve. Kept around so they
+    // can be exported and looked into.
+    unknown: sync::Mutex<collections::BTreeSet<u32>>,
+    // Whether the game's hash matched ours, by the game's hash.
+    checked: sync::Mutex<collections::BTreeMap<u32, bool>>,
+}
+
+impl HashDictionary {
+    /// Loads a gzipped name list from disk.
+    pub fn load(path: &Path) -> Result<Self, DictionaryError> {
+        let file = fs::File::open(path)
+            .map_err(DictionaryError::FileOpen)?;
+
+        Self::from_reader(io::BufReader::new(GzDecoder::new(file)))
+    }
+
+    /// Builds a dictionary from an uncompressed name list.
+    pub fn from_reader(reader: impl BufRead) -> Result<Self, DictionaryError> {
+        let mut result = Self::default();
+
+        for line in reader.lines() {
+            let line = line.map_err(DictionaryError::FileRead)?;
+            let name = line.trim();
+            if name.is_empty() || name.starts_with('#') {
+                continue;
+            }
+
+            result.insert(name);
+        }
+
+        Ok(result)
+    }
+
+    /// Adds a name to the dictionary. The first name wins if two names end
+    /// up with the same hash.
+    pub fn insert(&mut self, name: &str) {
+        let hash = hash_resource_name(name);
+        match self.names.get(&hash) {
+            Some(existing) if existing != name => self.collisions += 1,
+            Some(_) => {},
+            None => {
+                self.names.insert(hash, name.to_string());
+            },
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.names.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.names.is_empty()
+    }
+
+    /// Amount of names that were dropped because their hash was taken.
+    pub fn collisions(&self) -> usize {
+        self.collisions
+    }
+
+    /// Looks up the name for a hash. Unresolved hashes are recorded.
+    pub fn resolve(&self, hash: u32) -> Option<&str> {
+        let result = self.names.get(&hash).map(String::as_str);
+        if result.is_none() {
+            self.unknown.lock().unwrap().insert(hash);
+        }
+
+        result
+    }
+
+    /// Compares the game's hash for a name against ours, if the game still
+    /// has both.
+    pub fn check_hash(&self, name: &FD4BasicHashString) {
+        if let Some(matches) = name.hash_matches_name() {
+            self.checked.lock().unwrap().insert(name.hash, matches);
+        }
+    }
+
+    /// How many distinct hashes from the game matched ours and how many
+    /// didn't.
+    pub fn hash_checks(&self) -> (usize, usize) {
+        let checked = self.checked.lock().unwrap();
+        let matched = checked.values().filter(|matches| **matches).count();
+        (matched, checked.len() - matched)
+    }
+
+    /// Whether the game's hashes have been seen to match ours.
+    pub fn is_hash_verified(&self) -> bool {
+        matches!(self.hash_checks(), (1.., 0))
+    }
+
+    /// All hashes that were passed to resolve without yielding a name.
+    pub fn unknown_hashes(&self) -> Vec<u32> {
+        self.unknown.lock().unwrap().iter().copied().collect()
+    }
+
+    /// Gets a printable name for a hash string. Uses the string if the game
+    /// still has it and falls back to the dictionary otherwise.
+    pub fn display_name(&self, name: &FD4BasicHashString) -> String {
+        let string = name.string.to_string();
+        if !string.is_empty() {
+            self.check_hash(name);
+            return string;
+        }
+
+        let hash = name.computed_hash();
+        match self.resolve(hash) {
+            Some(resolved) if self.is_hash_verified() => resolved.to_string(),
+            Some(resolved) => format!("{resolved} (unverified)"),
+            None => format!("<unknown {hash:08x}>"),
+        }
+    }
+}
+
+/// Returns the global dictionary, loading it from the configured path on
+/// first use. A missing or broken dictionary file results in an empty
+/// dictionary.
+pub fn get_dictionary() -> &'static HashDictionary {
+    DICTIONARY.get_or_init(|| {
+        HashDictionary::load(&config::resolve_path(&config::current().dictionary))
+            .unwrap_or_else(|e| {
+                tracing::warn!("Could not load hash dictionary: {e:?}");
+                HashDictionary::default()
+            })
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use std::io::Write;
+
+    use flate2::write::GzEncoder;
+    use flate2::Compression;
+
+    use crate::game::fd4::{hash_resource_name, FD4BasicHashString};
+    use crate::util::hash_dictionary::HashDictionary;
+
+    const NAMES: &str = "# Characters\nc0000\nc2010\n\n  c4290  \nc0000\n";
+
+    #[test]
+    pub fn dictionary_resolves_names() {
+        let dictionary = HashDictionary::from_reader(NAMES.as_bytes()).unwrap();
+
+        assert_eq!(dictionary.len(), 3);
+        assert_eq!(dictionary.collisions(), 0);
+        assert_eq!(dictionary.resolve(hash_resource_name("c2010")), Some("c2010"));
+        assert_eq!(dictionary.resolve(hash_resource_name("C4290")), Some("c4290"));
+        assert!(dictionary.unknown_hashes().is_empty());
+    }
+
+    #[test]
+    pub fn dictionary_records_unknown_hashes() {
+        let dictionary = HashDictionary::from_reader(NAMES.as_bytes()).unwrap();
+
+        assert_eq!(dictionary.resolve(0x392fa297), None);
+        assert_eq!(dictionary.resolve(0x6f10fb8f), None);
+        assert_eq!(dictionary.resolve(0x392fa297), None);
+        assert_eq!(dictionary.unknown_hashes(), vec![0x392fa297, 0x6f10fb8f]);
+
+        let name = FD4BasicHashString::from_hash(0x6f10fb8f);
+        assert_eq!(dictionary.display_name(&name), "<unknown 6f10fb8f>");
+
+        let name = FD4BasicHashString::from_name("c0000");
+        assert_eq!(dictionary.display_name(&name), "c0000");
+    }
+
+    #[test]
+    pub fn dictionary_checks_hashes() {
+        let dictionary = HashDictionary::from_reader(NAMES.as_bytes()).unwrap();
+        let hashed = FD4BasicHashString::from_hash(hash_resource_name("c2010"));
+        assert!(!dictionary.is_hash_verified());
+        assert_eq!(dictionary.display_name(&hashed), "c2010 (unverified)");
+
+        // A name the game hashed differently than we do.
+        let mut mismatched = FD4BasicHashString::from_name("c4290");
+        mismatched.hash ^= 1;
+        assert_eq!(dictionary.display_name(&mismatched), "c4290");
+        assert_eq!(dictionary.hash_checks(), (0, 1));
+
+        dictionary.display_name(&FD4BasicHashString::from_name("c0000"));
+        assert_eq!(dictionary.hash_checks(), (1, 1));
+        assert!(!dictionary.is_hash_verified());
+
+        let dictionary = HashDictionary::from_reader(NAMES.as_bytes()).unwrap();
+        dictionary.check_hash(&FD4BasicHashString::from_name("c0000"));
+        // Names that haven't been hashed yet don't count.
+        let mut unhashed = FD4BasicHashString::from_name("c0000");
+        unhashed.needs_hashing = 1;
+        dictionary.check_hash(&unhashed);
+        assert_eq!(dictionary.hash_checks(), (1, 0));
+        assert_eq!(dictionary.display_name(&hashed), "c2010");
+    }
+
+    #[test]
+    pub fn dictionary_loads_gzipped_list() {
+        let path = std::env::temp_dir().join("eldenring_hash_dictionary_test.txt.gz");
+
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(NAMES.as_bytes()).unwrap();
+        std::fs::write(&path, encoder.finish().unwrap()).unwrap();
+
+        let dictionary = HashDictionary::load(&path).unwrap();
+        assert_eq!(dictionary.len(), 3);
+        assert_eq!(dictionary.resolve(hash_resource_name("c0000")), Some("c0000"));
+
+        std::fs::remove_file(path).unwrap();
+    }
+}
@@ -0,0 +1,203 @@
+mod binding;
+mod keyboard;
+
+pub use binding::*;
+pub use keyboard::*;
+
+use windows::Win32::Foundation::POINT;
+use windows::Win32::UI::Input::KeyboardAndMouse::GetAsyncKeyState;
+use windows::Win32::UI::WindowsAndMessaging::GetCursorPos;
+
+/// Checks if a key is currently held down. Takes a virtual-key code, mouse
+/// buttons have virtual-key codes too.
+pub fn is_key_down(virtual_key: u16) -> bool {
+    unsafe { GetAsyncKeyState(virtual_key as i32) as u16 & 0x8000 != 0 }
+}
+
+/// Position of the cursor in screen coordinates.
+pub fn cursor_position() -> Option<(i32, i32)> {
+    let mut point = POINT::default();
+    unsafe { GetCursorPos(&mut point) }.ok()?;
+    Some((point.x, point.y))
+}
+
+/// Turns cursor positions into movement since the last poll.
+#[derive(Default)]
+pub struct CursorDelta {
+    last: Option<(i32, i32)>,
+}
+
+impl CursorDelta {
+    pub fn update(&mut self, position: Option<(i32, i32)>) -> (i32, i32) {
+        let delta = self.last
+            .zip(position)
+            .map(|(last, position)| (position.0 - last.0, position.1 - last.1))
+            .unwrap_or_default();
+
+        self.last = position;
+        delta
+    }
+
+    /// Forgets the last position so the next update starts over.
+    pub fn reset(&mut self) {
+        self.last = None;
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::util::input::{
+        key_from_name, key_name, CursorDelta, KeyBinding, KeyBindingParseError, KeyboardState, Modifiers,
+        KEY_CONTROL, KEY_SHIFT,
+    };
+
+    /// Feeds the keyboard a frame where only the given keys are held.
+    fn hold(keyboard: &mut KeyboardState, keys: &[u16]) {
+        keyboard.update(|virtual_key| keys.contains(&virtual_key));
+    }
+
+    #[test]
+    fn keyboard_state_works() {
+        let mut keyboard = KeyboardState::default();
+        hold(&mut keyboard, &[]);
+        assert!(!keyboard.is_down(0x21));
+        assert!(!keyboard.is_pressed(0x21));
+
+        hold(&mut keyboard, &[0x21]);
+        assert!(keyboard.is_down(0x21));
+        assert!(keyboard.is_pressed(0x21));
+
+        // Holding a key only counts as a single press.
+        hold(&mut keyboard, &[0x21, 0x22]);
+        assert!(keyboard.is_down(0x21));
+        assert!(!keyboard.is_pressed(0x21));
+        assert!(keyboard.is_pressed(0x22));
+
+        hold(&mut keyboard, &[0x22]);
+        assert!(keyboard.is_released(0x21));
+        assert!(!keyboard.is_released(0x22));
+
+        hold(&mut keyboard, &[0x21, 0x22]);
+        assert!(keyboard.is_pressed(0x21));
+
+        // Codes outside of the virtual-key range are never down.
+        assert!(!keyboard.is_down(0x100));
+    }
+
+    #[test]
+    fn keyboard_capture_works() {
+        let mut keyboard = KeyboardState::default();
+        keyboard.set_captured(true);
+        hold(&mut keyboard, &[0x41]);
+        assert!(!keyboard.is_down(0x41));
+        assert!(!keyboard.is_pressed(0x41));
+
+        // A key that went down while captured isn't a press afterwards.
+        hold(&mut keyboard, &[0x41]);
+        keyboard.set_captured(false);
+        assert!(keyboard.is_down(0x41));
+        assert!(!keyboard.is_pressed(0x41));
+    }
+
+    #[test]
+    fn key_names_work() {
+        assert_eq!(key_from_name("a"), Some(0x41));
+        assert_eq!(key_from_name("7"), Some(0x37));
+        assert_eq!(key_from_name("f1"), Some(0x70));
+        assert_eq!(key_from_name("F24"), Some(0x87));
+        assert_eq!(key_from_name("F25"), None);
+        assert_eq!(key_from_name("F0"), None);
+        assert_eq!(key_from_name("Numpad5"), Some(0x65));
+        assert_eq!(key_from_name("pgup"), Some(0x21));
+        assert_eq!(key_from_name("Home"), Some(0x24));
+        assert_eq!(key_from_name("0x2d"), Some(0x2d));
+        assert_eq!(key_from_name("0x100"), None);
+        assert_eq!(key_from_name("Nope"), None);
+
+        for virtual_key in 0..0x100 {
+            if let Some(name) = key_name(virtual_key) {
+                assert_eq!(key_from_name(&name), Some(virtual_key), "{name}");
+            }
+        }
+    }
+
+    #[test]
+    fn key_binding_parsing_works() {
+        let binding = "Ctrl+Shift+F1".parse::<KeyBinding>().unwrap();
+        assert_eq!(binding.key, 0x70);
+        assert_eq!(binding.modifiers, Modifiers { ctrl: true, shift: true, alt: false });
+        assert_eq!(binding.to_string(), "Ctrl+Shift+F1");
+
+        assert_eq!(" alt + home ".parse(), Ok(KeyBinding::with_modifiers(0x24, Modifiers { alt: true, ..Modifiers::NONE })));
+        assert_eq!("Shift".parse(), Ok(KeyBinding::new(KEY_SHIFT)));
+        assert_eq!("Ctrl+0xe9".parse::<KeyBinding>().unwrap().to_string(), "Ctrl+0xe9");
+
+        assert_eq!("".parse::<KeyBinding>(), Err(KeyBindingParseError::Empty));
+        assert_eq!("Ctrl+".parse::<KeyBinding>(), Err(KeyBindingParseError::Empty));
+        assert_eq!("+A".parse::<KeyBinding>(), Err(KeyBindingParseError::Empty));
+        assert_eq!("Ctrl+Nope".parse::<KeyBinding>(), Err(KeyBindingParseError::UnknownKey("Nope".to_string())));
+        assert_eq!("A+B".parse::<KeyBinding>(), Err(KeyBindingParseError::NotAModifier("A".to_string())));
+        assert_eq!("Ctrl+Control+A".parse::<KeyBinding>(), Err(KeyBindingParseError::DuplicateModifier("Control".to_string())));
+    }
+
+    #[test]
+    fn key_binding_serde_works() {
+        #[derive(serde::Deserialize)]
+        struct Bindings {
+            toggle: KeyBinding,
+        }
+
+        let bindings = toml::from_str::<Bindings>("toggle = \"Ctrl+Home\"").unwrap();
+        assert_eq!(bindings.toggle, KeyBinding::with_modifiers(0x24, Modifiers { ctrl: true, ..Modifiers::NONE }));
+
+        let error = toml::from_str::<Bindings>("toggle = \"Hyper+Home\"").err().unwrap();
+        assert!(error.to_string().contains("unknown key \"Hyper\""), "{error}");
+    }
+
+    #[test]
+    fn chords_work() {
+        let mut keyboard = KeyboardState::default();
+        let plain = KeyBinding::new(0x70);
+        let chord = "Ctrl+F1".parse::<KeyBinding>().unwrap();
+
+        hold(&mut keyboard, &[0x70]);
+        assert!(plain.is_pressed(&keyboard));
+        assert!(!chord.is_pressed(&keyboard));
+
+        hold(&mut keyboard, &[]);
+        hold(&mut keyboard, &[KEY_CONTROL, 0x70]);
+        assert!(!plain.is_pressed(&keyboard));
+        assert!(chord.is_pressed(&keyboard));
+
+        // The modifier has to be held before the key goes down.
+        hold(&mut keyboard, &[]);
+        hold(&mut keyboard, &[0x70]);
+        hold(&mut keyboard, &[KEY_CONTROL, 0x70]);
+        assert!(!chord.is_pressed(&keyboard));
+        assert!(chord.is_down(&keyboard));
+
+        // Held bindings don't mind extra modifiers.
+        let forward = KeyBinding::new(0x57);
+        hold(&mut keyboard, &[KEY_SHIFT, 0x57]);
+        assert!(forward.is_down(&keyboard));
+        assert!(!forward.is_pressed(&keyboard));
+
+        // Binding on a modifier itself.
+        let shift = KeyBinding::new(KEY_SHIFT);
+        hold(&mut keyboard, &[]);
+        hold(&mut keyboard, &[KEY_SHIFT]);
+        assert!(shift.is_pressed(&keyboard));
+    }
+
+    #[test]
+    fn cursor_delta_works() {
+        let mut cursor = CursorDelta::default();
+        assert_eq!(cursor.update(Some((100, 100))), (0, 0));
+        assert_eq!(cursor.update(Some((110, 95))), (10, -5));
+        assert_eq!(cursor.update(None), (0, 0));
+        assert_eq!(cursor.update(Some((0, 0))), (0, 0));
+
+        cursor.reset();
+        assert_eq!(cursor.update(Some((50, 50))), (0, 0));
+    }
+}
@@ -0,0 +1,253 @@
+use std::fmt;
+use std::str::FromStr;
+
+use serde::{Deserialize, Serialize};
+
+use crate::util::input::KeyboardState;
+
+pub const KEY_SHIFT: u16 = 0x10;
+pub const KEY_CONTROL: u16 = 0x11;
+pub const KEY_ALT: u16 = 0x12;
+
+/// Names for virtual-key codes that aren't letters, digits, function keys or
+/// numpad digits, those are generated.
+const KEY_NAMES: &[(&str, u16)] = &[
+    ("MouseLeft", 0x01),
+    ("MouseRight", 0x02),
+    ("MouseMiddle", 0x04),
+    ("Mouse4", 0x05),
+    ("Mouse5", 0x06),
+    ("Backspace", 0x08),
+    ("Tab", 0x09),
+    ("Enter", 0x0d),
+    ("Shift", KEY_SHIFT),
+    ("Ctrl", KEY_CONTROL),
+    ("Alt", KEY_ALT),
+    ("Pause", 0x13),
+    ("CapsLock", 0x14),
+    ("Escape", 0x1b),
+    ("Space", 0x20),
+    ("PageUp", 0x21),
+    ("PageDown", 0x22),
+    ("End", 0x23),
+    ("Home", 0x24),
+    ("Left", 0x25),
+    ("Up", 0x26),
+    ("Right", 0x27),
+    ("Down", 0x28),
+    ("Insert", 0x2d),
+    ("Delete", 0x2e),
+    ("Multiply", 0x6a),
+    ("Add", 0x6b),
+    ("Subtract", 0x6d),
+    ("Decimal", 0x6e),
+    ("Divide", 0x6f),
+];
+
+/// Other spellings accepted when parsing.
+const KEY_ALIASES: &[(&str, u16)] = &[
+    ("Control", KEY_CONTROL),
+    ("Esc", 0x1b),
+    ("Return", 0x0d),
+    ("PgUp", 0x21),
+    ("PgDn", 0x22),
+    ("Ins", 0x2d),
+    ("Del", 0x2e),
+];
+
+/// Name of a virtual-key code as used in bindings.
+pub fn key_name(virtual_key: u16) -> Option<String> {
+    let name = match virtual_key {
+        0x30..=0x39 | 0x41..=0x5a => char::from(virtual_key as u8).to_string(),
+        0x60..=0x69 => format!("Numpad{}", virtual_key - 0x60),
+        0x70..=0x87 => format!("F{}", virtual_key - 0x6f),
+        _ => KEY_NAMES.iter()
+            .find(|(_, key)| *key == virtual_key)?
+            .0
+            .to_string(),
+    };
+
+    Some(name)
+}
+
+/// Looks up a key by name, ignoring case. Also takes raw codes like `0x24`.
+pub fn key_from_name(name: &str) -> Option<u16> {
+    if let Some(hex) = name.strip_prefix("0x").or_else(|| name.strip_prefix("0X")) {
+        return u8::from_str_radix(hex, 16).ok().map(u16::from);
+    }
+
+    let upper = name.to_ascii_uppercase();
+    if let [c] = upper.as_bytes() {
+        if c.is_ascii_alphanumeric() {
+            return Some(u16::from(*c));
+        }
+    }
+
+    let numbered = |prefix: &str| {
+        upper.strip_prefix(prefix)
+            .and_then(|number| number.parse::<u16>().ok())
+    };
+    if let Some(number) = numbered("NUMPAD").filter(|number| *number <= 9) {
+        return Some(0x60 + number);
+    }
+    if let Some(number) = numbered("F").filter(|number| (1..=24).contains(number)) {
+        return Some(0x6f + number);
+    }
+
+    KEY_NAMES.iter()
+        .chain(KEY_ALIASES)
+        .find(|(key_name, _)| key_name.eq_ignore_ascii_case(name))
+        .map(|(_, key)| *key)
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Modifiers {
+    pub ctrl: bool,
+    pub shift: bool,
+    pub alt: bool,
+}
+
+impl Modifiers {
+    pub const NONE: Self = Self { ctrl: false, shift: false, alt: false };
+
+    /// Checks if every modifier in other is also in self.
+    pub fn contains(&self, other: Modifiers) -> bool {
+        (self.ctrl || !other.ctrl) && (self.shift || !other.shift) && (self.alt || !other.alt)
+    }
+
+    /// Drops the modifier that is the key itself, holding shift shouldn't
+    /// count as shift being a modifier for a binding on shift.
+    fn without_key(mut self, virtual_key: u16) -> Self {
+        match virtual_key {
+            KEY_CONTROL => self.ctrl = false,
+            KEY_SHIFT => self.shift = false,
+            KEY_ALT => self.alt = false,
+            _ => {},
+        }
+
+        self
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum KeyBindingParseError {
+    Empty,
+    UnknownKey(String),
+    /// Only the last part of a binding may be something other than a
+    /// modifier.
+    NotAModifier(String),
+    DuplicateModifier(String),
+}
+
+impl fmt::Display for KeyBindingParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Empty => write!(f, "binding is empty"),
+            Self::UnknownKey(key) => write!(f, "unknown key \"{key}\""),
+            Self::NotAModifier(key) => write!(f, "\"{key}\" is not a modifier, only the last key can be"),
+            Self::DuplicateModifier(key) => write!(f, "modifier \"{key}\" appears more than once"),
+        }
+    }
+}
+
+impl std::error::Error for KeyBindingParseError {}
+
+/// A key with the modifiers that have to be held along with it. Written as
+/// the modifiers followed by the key, like `Ctrl+Shift+F1`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct KeyBinding {
+    pub modifiers: Modifiers,
+    pub key: u16,
+}
+
+impl KeyBinding {
+    pub const fn new(key: u16) -> Self {
+        Self { modifiers: Modifiers::NONE, key }
+    }
+
+    pub const fn with_modifiers(key: u16, modifiers: Modifiers) -> Self {
+        Self { modifiers, key }
+    }
+
+    /// Checks if the key went down this frame with exactly the binding's
+    /// modifiers held, so `Ctrl+F1` doesn't also trigger `F1`.
+    pub fn is_pressed(&self, keyboard: &KeyboardState) -> bool {
+        keyboard.is_pressed(self.key)
+            && keyboard.modifiers().without_key(self.key) == self.modifiers
+    }
+
+    /// Checks if the key is held with at least the binding's modifiers. Extra
+    /// modifiers are fine so movement keeps working while sprinting.
+    pub fn is_down(&self, keyboard: &KeyboardState) -> bool {
+        keyboard.is_down(self.key)
+            && keyboard.modifiers().contains(self.modifiers)
+    }
+}
+
+impl FromStr for KeyBinding {
+    type Err = KeyBindingParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parts = s.split('+').map(str::trim).collect::<Vec<_>>();
+        let key = match parts.pop() {
+            Some(key) if !key.is_empty() => key,
+            _ => return Err(KeyBindingParseError::Empty),
+        };
+
+        let mut modifiers = Modifiers::NONE;
+        for part in parts {
+            let modifier = match key_from_name(part) {
+                Some(KEY_CONTROL) => &mut modifiers.ctrl,
+                Some(KEY_SHIFT) => &mut modifiers.shift,
+                Some(KEY_ALT) => &mut modifiers.alt,
+                Some(_) => return Err(KeyBindingParseError::NotAModifier(part.to_string())),
+                None if part.is_empty() => return Err(KeyBindingParseError::Empty),
+                None => return Err(KeyBindingParseError::UnknownKey(part.to_string())),
+            };
+
+            if *modifier {
+                return Err(KeyBindingParseError::DuplicateModifier(part.to_string()));
+            }
+            *modifier = true;
+        }
+
+        let key = key_from_name(key)
+            .ok_or_else(|| KeyBindingParseError::UnknownKey(key.to_string()))?;
+
+        Ok(Self::with_modifiers(key, modifiers))
+    }
+}
+
+impl TryFrom<String> for KeyBinding {
+    type Error = KeyBindingParseError;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        value.parse()
+    }
+}
+
+impl From<KeyBinding> for String {
+    fn from(value: KeyBinding) -> Self {
+        value.to_string()
+    }
+}
+
+impl fmt::Display for KeyBinding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let modifiers = [
+            (self.modifiers.ctrl, "Ctrl"),
+            (self.modifiers.shift, "Shift"),
+            (self.modifiers.alt, "Alt"),
+        ];
+
+        for (_, name) in modifiers.iter().filter(|(held, _)| *held) {
+            write!(f, "{name}+")?;
+        }
+
+        match key_name(self.key) {
+            Some(name) => write!(f, "{name}"),
+            None => write!(f, "{:#04x}", self.key),
+        }
+    }
+}
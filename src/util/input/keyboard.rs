@@ -0,0 +1,77 @@
+use crate::util::input::{self, Modifiers, KEY_ALT, KEY_CONTROL, KEY_SHIFT};
+
+const KEY_COUNT: usize = 0x100;
+
+/// State of every virtual key, sampled once per frame so presses and releases
+/// can be told apart from keys being held.
+pub struct KeyboardState {
+    current: [bool; KEY_COUNT],
+    previous: [bool; KEY_COUNT],
+    captured: bool,
+}
+
+impl Default for KeyboardState {
+    fn default() -> Self {
+        Self {
+            current: [false; KEY_COUNT],
+            previous: [false; KEY_COUNT],
+            captured: false,
+        }
+    }
+}
+
+impl KeyboardState {
+    /// Samples every key through is_down, the previous sample becomes the
+    /// last frame.
+    pub fn update(&mut self, mut is_down: impl FnMut(u16) -> bool) {
+        self.previous = self.current;
+        for (virtual_key, down) in self.current.iter_mut().enumerate() {
+            *down = is_down(virtual_key as u16);
+        }
+    }
+
+    /// Samples the actual keyboard and mouse buttons.
+    pub fn poll(&mut self) {
+        self.update(input::is_key_down);
+    }
+
+    /// While captured the overlay has focus and every key reads as up, so
+    /// typing into the overlay doesn't trigger hotkeys. Keys are still
+    /// sampled so nothing registers as pressed once the capture ends.
+    pub fn set_captured(&mut self, captured: bool) {
+        self.captured = captured;
+    }
+
+    pub fn is_captured(&self) -> bool {
+        self.captured
+    }
+
+    pub fn is_down(&self, virtual_key: u16) -> bool {
+        !self.captured && self.sample(&self.current, virtual_key)
+    }
+
+    /// Checks if the key went down since the last frame.
+    pub fn is_pressed(&self, virtual_key: u16) -> bool {
+        self.is_down(virtual_key) && !self.sample(&self.previous, virtual_key)
+    }
+
+    /// Checks if the key went up since the last frame.
+    pub fn is_released(&self, virtual_key: u16) -> bool {
+        !self.captured
+            && !self.sample(&self.current, virtual_key)
+            && self.sample(&self.previous, virtual_key)
+    }
+
+    /// Modifiers that are currently held.
+    pub fn modifiers(&self) -> Modifiers {
+        Modifiers {
+            ctrl: self.is_down(KEY_CONTROL),
+            shift: self.is_down(KEY_SHIFT),
+            alt: self.is_down(KEY_ALT),
+        }
+    }
+
+    fn sample(&self, keys: &[bool; KEY_COUNT], virtual_key: u16) -> bool {
+        keys.get(virtual_key as usize).copied().unwrap_or(false)
+    }
+}
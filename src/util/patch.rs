@@ -1,6 +1,11 @@
 use std::ptr;
 
-pub fn new_patch<const N: usize>(
+use windows::core::HRESULT;
+
+/// # Safety
+/// The target has to be valid to read and write N bytes for as long as the
+/// patch exists, no matter which thread the patch ends up on.
+pub unsafe fn new_patch<const N: usize>(
     target: *mut u8,
     replacement: [u8; N],
 ) -> Patch<InactiveState<N>> {
@@ -12,6 +17,12 @@ pub fn new_patch<const N: usize>(
     }
 }
 
+#[derive(Debug)]
+pub enum PatchError {
+    /// The target's page protection couldn't be lifted, nothing was written.
+    Unprotect(HRESULT),
+}
+
 trait PatchState {}
 
 #[derive(Debug)]
@@ -20,6 +31,13 @@ pub struct Patch<TState: PatchState> {
     inner: TState,
 }
 
+// The target is only ever written through apply and rollback, which take
+// &mut self, so sharing a patch between threads can't race on it. Sending
+// one is fine as new_patch requires the target to be valid from any thread
+// for the lifetime of the patch.
+unsafe impl<TState: PatchState> Send for Patch<TState> {}
+unsafe impl<TState: PatchState> Sync for Patch<TState> {}
+
 #[derive(Debug)]
 pub struct InactiveState<const N: usize> {
     replacement: [u8; N],
@@ -29,7 +47,7 @@ impl<const N: usize> PatchState for InactiveState<N> {}
 
 impl<const N: usize> Patch<InactiveState<N>> {
     /// Applies memory edit represented by this patch.
-    pub fn apply(&mut self) -> Patch<ActiveState<N>> {
+    pub fn apply(&mut self) -> Result<Patch<ActiveState<N>>, PatchError> {
         let mut original = [0x0u8; N];
         unsafe {
             // Backup the original
@@ -40,21 +58,16 @@ impl<const N: usize> Patch<InactiveState<N>> {
             );
 
             // Copy in the provided bytes
-            ptr::copy_nonoverlapping(
-                self.inner.replacement.as_ptr(),
-                self.target,
-                N
-            );
-
+            backend::write(self.target, &self.inner.replacement)?;
         }
     
-        Patch {
+        Ok(Patch {
             target: self.target,
             inner: ActiveState {
                 replacement: self.inner.replacement,
                 original,
             }
-        }
+        })
     }
 }
 
@@ -68,21 +81,70 @@ impl<const N: usize> PatchState for ActiveState<N> {}
 
 impl<const N: usize> Patch<ActiveState<N>> {
     /// Rolls back the memory edits made by this patch.
-    pub fn rollback(&mut self) -> Patch<InactiveState<N>> {
+    pub fn rollback(&mut self) -> Result<Patch<InactiveState<N>>, PatchError> {
         unsafe {
-            ptr::copy_nonoverlapping(
-                self.inner.original.as_ptr(),
-                self.target,
-                N
-            );
+            backend::write(self.target, &self.inner.original)?;
         }
 
-        Patch {
+        Ok(Patch {
             target: self.target,
             inner: InactiveState {
                 replacement: self.inner.replacement,
             }
+        })
+    }
+}
+
+// Code is mapped read-only so the page protection has to be lifted for the
+// duration of the write.
+#[cfg(not(test))]
+mod backend {
+    use std::ffi;
+    use std::ptr;
+
+    use windows::Win32::System::Diagnostics::Debug::FlushInstructionCache;
+    use windows::Win32::System::Memory::{VirtualProtect, PAGE_EXECUTE_READWRITE, PAGE_PROTECTION_FLAGS};
+    use windows::Win32::System::Threading::GetCurrentProcess;
+
+    use super::PatchError;
+
+    pub unsafe fn write(target: *mut u8, bytes: &[u8]) -> Result<(), PatchError> {
+        let mut old_protection = PAGE_PROTECTION_FLAGS::default();
+        VirtualProtect(
+            target as *const ffi::c_void,
+            bytes.len(),
+            PAGE_EXECUTE_READWRITE,
+            &mut old_protection,
+        ).map_err(|e| PatchError::Unprotect(e.code()))?;
+
+        ptr::copy_nonoverlapping(bytes.as_ptr(), target, bytes.len());
+
+        // The write went through either way, leaving the page writable is
+        // only worth a warning.
+        if let Err(e) = VirtualProtect(
+            target as *const ffi::c_void,
+            bytes.len(),
+            old_protection,
+            &mut old_protection,
+        ) {
+            tracing::warn!("Could not restore patch target protection: {e:?}");
         }
+
+        let _ = FlushInstructionCache(GetCurrentProcess(), Some(target as *const ffi::c_void), bytes.len());
+        Ok(())
+    }
+}
+
+// Tests patch regular buffers that are writable to begin with.
+#[cfg(test)]
+mod backend {
+    use std::ptr;
+
+    use super::PatchError;
+
+    pub unsafe fn write(target: *mut u8, bytes: &[u8]) -> Result<(), PatchError> {
+        ptr::copy_nonoverlapping(bytes.as_ptr(), target, bytes.len());
+        Ok(())
     }
 }
 
@@ -94,16 +156,16 @@ mod test {
     pub fn patch_works() {
         let mut target: [u8; 4] =  [0x12, 0x34, 0x56, 0x78];
 
-        let mut patch = patch::new_patch(target.as_mut_ptr(), [0x99, 0x99]);
+        let mut patch = unsafe { patch::new_patch(target.as_mut_ptr(), [0x99, 0x99]) };
         assert_eq!([0x12, 0x34, 0x56, 0x78], target);
 
-        let mut patch = patch.apply();
+        let mut patch = patch.apply().unwrap();
         assert_eq!([0x99, 0x99, 0x56, 0x78], target);
 
-        let mut patch = patch.rollback();
+        let mut patch = patch.rollback().unwrap();
         assert_eq!([0x12, 0x34, 0x56, 0x78], target);
 
-        patch.apply();
+        patch.apply().unwrap();
         assert_eq!([0x99, 0x99, 0x56, 0x78], target);
     }
 }
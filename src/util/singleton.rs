@@ -9,7 +9,7 @@ use broadsword::scanner;
 use crate::export::Export;
 
 pub type SingletonMap = collections::HashMap<String, usize>;
-static SINGLETON_MAP: sync::OnceLock<SingletonMap> = sync::OnceLock::new();
+static SINGLETON_MAP: sync::OnceLock<Result<SingletonMap, SingletonMapError>> = sync::OnceLock::new();
 
 #[derive(Debug)]
 pub enum SingletonMapError {
@@ -21,7 +21,8 @@ pub enum SingletonMapError {
 #[derive(Debug)]
 pub enum LookupError {
     NotFound,
-    SingletonMapCreation(SingletonMapError),
+    /// Building the map failed the first time around, it isn't retried.
+    SingletonMapCreation(&'static SingletonMapError),
 }
 
 pub trait DLRFLocatable {
@@ -35,11 +36,9 @@ pub trait DLRFLocatable {
 /// Result<Option<T>, E>. An example of such is WorldChrMan of which an 
 /// instance only exists if you're actually in the game world.
 pub fn get_instance<T: DLRFLocatable>() -> Result<Option<&'static mut T>, LookupError> {
-    let table = SINGLETON_MAP.get_or_init(
-        || build_singleton_table()
-            .map_err(LookupError::SingletonMapCreation)
-            .expect("Could not create singleton map")
-    );
+    let table = SINGLETON_MAP.get_or_init(build_singleton_table)
+        .as_ref()
+        .map_err(LookupError::SingletonMapCreation)?;
 
     let ptr = table.get(T::DLRF_NAME)
         .map(usize::to_owned)
@@ -131,8 +130,9 @@ fn build_singleton_table() -> Result<SingletonMap, SingletonMapError> {
         results.insert(name, static_address);
     }
 
-    Export::export(&results)
-        .expect("Could not export CSV");
+    if let Err(e) = Export::export(&results) {
+        tracing::error!("Could not export singletons: {e:?}");
+    }
 
     Ok(results)
 }
@@ -143,7 +143,7 @@ pub enum SectionLookupError {
     SectionNotFound,
 }
 
-fn get_section(
+pub(crate) fn get_section(
     section: &str,
 ) -> Result<(ops::Range<usize>, &[u8]), SectionLookupError> {
     let module = get_game_module()
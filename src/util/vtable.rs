@@ -0,0 +1,105 @@
+/// Declares a game vftable and the virtual calls it offers.
+///
+/// Slots are declared by their index in the table so that unknown or unused
+/// slots can simply be left out. Every slot becomes an unsafe accessor on the
+/// vftable type returning the typed `extern "C"` function pointer, as well as
+/// a method on the owning type that calls through its `vftable` field with
+/// itself as the `this` pointer. Slots prefixed with `unsafe` get an unsafe
+/// method, which should be used for anything that frees or otherwise
+/// invalidates the instance.
+///
+/// ```text
+/// vtable! {
+///     pub struct CSFileVMT for CSFile<'_> {
+///         1 => unsafe fn destructor(&self, flags: u32) -> *const ffi::c_void;
+///         2 => fn get_repository_1_resource(&self, name: *const FD4BasicHashString) -> *const FD4ResCap<'static, ()>;
+///     }
+/// }
+/// ```
+#[macro_export]
+macro_rules! vtable {
+    (
+        $(#[$meta:meta])*
+        $vis:vis struct $name:ident for $this:ty {
+            $($body:tt)*
+        }
+    ) => {
+        $(#[$meta])*
+        #[repr(transparent)]
+        #[derive(zerocopy::FromZeroes, zerocopy::FromBytes, Clone, Copy, PartialEq, Eq)]
+        $vis struct $name(usize);
+
+        impl $name {
+            pub const fn from_raw(address: usize) -> Self {
+                Self(address)
+            }
+
+            pub const fn as_raw(&self) -> usize {
+                self.0
+            }
+
+            /// Reads the function pointer in a slot.
+            ///
+            /// # Safety
+            /// The address must point to a vftable with more than index
+            /// slots. Only null is checked for.
+            pub unsafe fn slot(&self, index: usize) -> usize {
+                assert!(self.0 != 0, concat!("Called into a null ", stringify!($name)));
+                unsafe { *(self.0 as *const usize).add(index) }
+            }
+        }
+
+        $crate::vtable!(@slot $name, $this, $($body)*);
+    };
+
+    (@slot $name:ident, $this:ty,) => {};
+
+    (@slot $name:ident, $this:ty,
+        $(#[$fmeta:meta])*
+        $index:literal => unsafe fn $method:ident(&self $(, $arg:ident: $arg_ty:ty)* $(,)?) $(-> $ret:ty)?;
+        $($rest:tt)*
+    ) => {
+        impl $name {
+            /// # Safety
+            /// Same as `slot`, the table has to have this slot.
+            pub unsafe fn $method(&self) -> extern "C" fn(*const $this $(, $arg_ty)*) $(-> $ret)? {
+                std::mem::transmute(self.slot($index))
+            }
+        }
+
+        impl $this {
+            $(#[$fmeta])*
+            #[allow(clippy::missing_safety_doc)]
+            pub unsafe fn $method(&self $(, $arg: $arg_ty)*) $(-> $ret)? {
+                (self.vftable.$method())(self $(, $arg)*)
+            }
+        }
+
+        $crate::vtable!(@slot $name, $this, $($rest)*);
+    };
+
+    (@slot $name:ident, $this:ty,
+        $(#[$fmeta:meta])*
+        $index:literal => fn $method:ident(&self $(, $arg:ident: $arg_ty:ty)* $(,)?) $(-> $ret:ty)?;
+        $($rest:tt)*
+    ) => {
+        impl $name {
+            /// # Safety
+            /// Same as `slot`, the table has to have this slot.
+            pub unsafe fn $method(&self) -> extern "C" fn(*const $this $(, $arg_ty)*) $(-> $ret)? {
+                std::mem::transmute(self.slot($index))
+            }
+        }
+
+        impl $this {
+            $(#[$fmeta])*
+            pub fn $method(&self $(, $arg: $arg_ty)*) $(-> $ret)? {
+                // A live instance points at its class' vftable, which has
+                // every declared slot.
+                unsafe { (self.vftable.$method())(self $(, $arg)*) }
+            }
+        }
+
+        $crate::vtable!(@slot $name, $this, $($rest)*);
+    };
+}